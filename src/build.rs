@@ -0,0 +1,227 @@
+//! Embed the bundle in a binary from its build script, so it can print its own third party notices without shipping a
+//! separate file.
+//!
+//! In the `main` of `build.rs`:
+//!
+//! ```no_run
+//! bundle_licenses_lib::build::generate(std::env::var_os("OUT_DIR").unwrap()).unwrap();
+//! ```
+//!
+//! And in the binary:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/licenses.rs"));
+//!
+//! fn print_licenses() {
+//!     print!("{}", licenses());
+//! }
+//! ```
+//!
+//! The generated module decompresses the bundle with `flate2`, which has to be a dependency of the binary.
+use std::{
+    collections::BTreeSet,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::MetadataCommand;
+use flate2::{write::GzEncoder, Compression};
+use thiserror::Error;
+
+use crate::{
+    bundle::{Bundle, BundleBuilder, BundleError},
+    config::{Config, ConfigError, CONFIG_FILE_NAME},
+    format::{Format, FormatError},
+};
+
+/// The name of the generated accessor module in the output directory.
+pub const MODULE_FILE_NAME: &str = "licenses.rs";
+/// The name of the compressed bundle in the output directory, without the format extension.
+const BLOB_FILE_STEM: &str = "licenses";
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+    #[error(transparent)]
+    CargoMetadata(#[from] cargo_metadata::Error),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Format(#[from] FormatError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("{0} is not set, generate has to be called from a build script")]
+    NotInBuildScript(&'static str),
+}
+
+/// Bundle the licenses of the package being built and write them to `out_dir` as a compressed YAML blob, along with an
+/// accessor module named [`MODULE_FILE_NAME`].
+///
+/// Options are read from the config of the package as by `cargo bundle-licenses`. Dependencies are limited to the
+/// target being built for unless the config lists targets, and to the features the package is built with in addition
+/// to the features of the config. The bundle has no timestamp so the build stays reproducible. Diagnostics are
+/// reported as build warnings.
+pub fn generate(out_dir: impl AsRef<Path>) -> Result<PathBuf, BuildError> {
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok_or(BuildError::NotInBuildScript("CARGO_MANIFEST_DIR"))?;
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    let mut config = Config::discover()?.unwrap_or_default();
+    if config.targets.is_empty() {
+        config.targets.extend(env::var("TARGET").ok());
+    }
+    for feature in enabled_features(&manifest_path)? {
+        if !config.features.contains(&feature) {
+            config.features.push(feature);
+        }
+    }
+
+    let lockfile = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lockfile| lockfile.exists());
+    // The config lives in the workspace root next to the lockfile, either in its own file or the root manifest
+    let workspace_root = lockfile.as_deref().and_then(Path::parent);
+    let inputs: BTreeSet<PathBuf> = vec![
+        Some(manifest_path.clone()),
+        lockfile.clone(),
+        workspace_root.map(|root| root.join("Cargo.toml")),
+        workspace_root.map(|root| root.join(CONFIG_FILE_NAME)),
+        config.previous.clone(),
+        config.overrides.clone(),
+        config.spdx_data.clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|input| input.exists())
+    .collect();
+    for input in inputs {
+        println!("cargo:rerun-if-changed={}", input.display());
+    }
+
+    let builder = BundleBuilder::from_config(&config)?
+        .manifest_path(&manifest_path)
+        .omit_timestamp(true);
+    generate_with(&builder, Format::Yaml, out_dir)
+}
+
+/// The features of the package at `manifest_path` that are enabled for the build script, besides `default`.
+fn enabled_features(manifest_path: &Path) -> Result<Vec<String>, BuildError> {
+    let metadata = MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .exec()?;
+    let Some(package) = metadata
+        .packages
+        .iter()
+        .find(|package| package.manifest_path.as_std_path() == manifest_path)
+    else {
+        return Ok(vec![]);
+    };
+    Ok(package
+        .features
+        .keys()
+        .filter(|feature| *feature != "default")
+        .filter(|feature| env::var_os(feature_env_var(feature)).is_some())
+        .cloned()
+        .collect())
+}
+
+/// The variable cargo sets for build scripts when a feature is enabled.
+fn feature_env_var(feature: &str) -> String {
+    format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"))
+}
+
+/// Run `builder` and write the bundle to `out_dir` in `format`, see [`generate`].
+///
+/// Unlike [`generate`], the features and the timestamp of the bundle are taken from `builder` as is.
+pub fn generate_with(
+    builder: &BundleBuilder,
+    format: Format,
+    out_dir: impl AsRef<Path>,
+) -> Result<PathBuf, BuildError> {
+    let (bundle, diagnostics) = builder.exec()?;
+    for diagnostic in &diagnostics {
+        println!("cargo:warning={diagnostic}");
+    }
+    embed(&bundle, format, out_dir.as_ref())
+}
+
+/// Write a compressed `bundle` and an accessor module for it to `out_dir`, returning the path of the module.
+pub fn embed(bundle: &Bundle, format: Format, out_dir: &Path) -> Result<PathBuf, BuildError> {
+    let extension = match format {
+        Format::Json => "json",
+        Format::Toml => "toml",
+        Format::Yaml => "yml",
+    };
+    let blob = out_dir.join(format!("{BLOB_FILE_STEM}.{extension}.gz"));
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(&blob)?), Compression::best());
+    format.serialize_to_writer(&mut encoder, bundle)?;
+    encoder.finish()?.flush()?;
+
+    let module = out_dir.join(MODULE_FILE_NAME);
+    fs::write(&module, module_source(&blob, extension))?;
+    Ok(module)
+}
+
+/// The source of the accessor module for the blob at `blob`.
+fn module_source(blob: &Path, extension: &str) -> String {
+    format!(
+        r#"// @generated by {generator}, do not edit.
+
+/// The third party licenses of this binary as a gzip compressed {extension} bundle.
+pub const LICENSES_GZ: &[u8] = include_bytes!({blob:?});
+
+/// The third party licenses of this binary as a {extension} bundle.
+pub fn licenses() -> String {{
+    use std::io::Read as _;
+
+    let mut licenses = String::new();
+    flate2::read::GzDecoder::new(LICENSES_GZ)
+        .read_to_string(&mut licenses)
+        .expect("embedded licenses are valid gzip");
+    licenses
+}}
+"#,
+        generator = env!("CARGO_PKG_NAME"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read as _;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn embed_round_trip() {
+        let out_dir = TempDir::new("embed");
+        let bundle = Bundle::new(&[], vec![]);
+
+        let module = embed(&bundle, Format::Json, &out_dir).unwrap();
+        let source = fs::read_to_string(&module).unwrap();
+        let blob = out_dir.join("licenses.json.gz");
+        assert!(source.contains(&format!("include_bytes!({blob:?})")));
+
+        let mut json = String::new();
+        GzDecoder::new(File::open(&blob).unwrap())
+            .read_to_string(&mut json)
+            .unwrap();
+        let decoded = Format::Json
+            .deserialize_from_reader(json.as_bytes())
+            .unwrap();
+        assert_eq!(decoded.root_name(), bundle.root_name());
+    }
+
+    #[test]
+    fn feature_env_vars() {
+        assert_eq!(feature_env_var("serde"), "CARGO_FEATURE_SERDE");
+        assert_eq!(feature_env_var("serde-json"), "CARGO_FEATURE_SERDE_JSON");
+    }
+}
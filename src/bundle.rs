@@ -1,17 +1,37 @@
 //! Find all LICENSE-like files in each packages source repo and match them with the
 //! the licenses specified in the Cargo.toml file.
 
-use std::{fmt::Write as _, str::FromStr as _};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    env,
+    fmt::Write as _,
+    mem,
+    path::{Path, PathBuf},
+    str::FromStr as _,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
+    cache::DiscoveryCache,
+    config::Config,
+    diagnostic::{locate_in_lockfile, Diagnostic, DiagnosticKind},
+    discovery::DiscoveryOptions,
     finalized_license::{
-        finalized_licenses_lookup, FinalizedLicense, LicenseKey, LICENSE_NOT_FOUNT_TEXT,
+        finalized_licenses_lookup, text_hash, FinalizedLicense, LicenseAndText, LicenseKey,
+        MetadataField, LICENSE_NOT_FOUNT_TEXT,
     },
     found_license::{FoundLicense, FoundLicenseError},
     license::License,
+    overrides::Overrides,
     package_loader::PackageLoader,
+    policy::Policy,
+    spdx_data::SpdxData,
 };
-use cargo_metadata::Package;
+use cargo_metadata::{semver::Version, Package};
+use rayon::{prelude::*, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -21,13 +41,45 @@ pub enum BundleError {
     FoundLicenseError(#[from] crate::found_license::FoundLicenseError),
     #[error(transparent)]
     PackageLoaderError(#[from] crate::package_loader::PackageLoaderError),
+    #[error(transparent)]
+    SpdxDataError(#[from] crate::spdx_data::SpdxDataError),
+    #[error(transparent)]
+    OverridesError(#[from] crate::overrides::OverridesError),
+    #[error(transparent)]
+    DiagnosticError(#[from] crate::diagnostic::DiagnosticError),
+    #[error(transparent)]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
+    #[error("License text {0} is not in the texts table of the bundle")]
+    MissingText(String),
+    #[error("Bundle schema version {0} is newer than the supported version {CURRENT_SCHEMA_VERSION}, update cargo-bundle-licenses")]
+    UnsupportedSchema(u32),
 }
 
+/// The newest schema version that can be read.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+/// The generator recorded in the metadata of a bundle unless set with [`BundleBuilder::generator`].
+const DEFAULT_GENERATOR: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
 #[derive(Clone, Debug, Default)]
 pub struct BundleBuilder {
     previous: Option<Bundle>,
     features: Vec<String>,
     prefer: Vec<License>,
+    exclude: Vec<String>,
+    deep_scan: bool,
+    discovery: DiscoveryOptions,
+    overrides: Overrides,
+    carry_forward: bool,
+    policy: Policy,
+    allow_warnings: Vec<String>,
+    match_details: bool,
+    targets: Vec<String>,
+    generator: Option<String>,
+    manifest_path: Option<PathBuf>,
+    jobs: Option<usize>,
+    cache: bool,
+    metadata_fields: Vec<MetadataField>,
+    omit_timestamp: bool,
 }
 
 impl BundleBuilder {
@@ -35,16 +87,68 @@ impl BundleBuilder {
         Self::default()
     }
 
+    /// Create a builder from the bundle options of a [`Config`].
+    pub fn from_config(config: &Config) -> Result<Self, BundleError> {
+        let mut builder = Self::new()
+            .features(&config.features)
+            .targets(&config.targets)
+            .prefer(&config.prefer)
+            .exclude(&config.exclude)
+            .carry_forward(config.carry_forward)
+            .deep_scan(config.deep_scan)
+            .license_file_patterns(&config.license_file_patterns)
+            .policy(config.policy.clone())
+            .allow_warnings(&config.allow_warnings)
+            .match_details(config.match_details)
+            .cache(!config.no_cache)
+            .metadata_fields(&config.metadata_fields);
+        if let Some(jobs) = config.jobs {
+            builder = builder.jobs(jobs);
+        }
+        if let Some(spdx_data) = &config.spdx_data {
+            builder = builder.spdx_data(SpdxData::new(spdx_data)?);
+        }
+        if let Some(overrides) = &config.overrides {
+            builder = builder.overrides(Overrides::from_path(overrides)?);
+        }
+        Ok(builder)
+    }
+
     pub fn previous(mut self, previous: &Bundle) -> Self {
         self.previous = Some(previous.clone());
         self
     }
 
+    /// Carry texts from the previous bundle forward from older versions of a package with an unchanged license,
+    /// instead of only using texts of the exact same package version.
+    pub fn carry_forward(mut self, carry_forward: bool) -> Self {
+        self.carry_forward = carry_forward;
+        self
+    }
+
     pub fn features(mut self, features: &[String]) -> Self {
         self.features = features.to_vec();
         self
     }
 
+    /// Only include dependencies for the given target triples.
+    pub fn targets(mut self, targets: &[String]) -> Self {
+        self.targets = targets.to_vec();
+        self
+    }
+
+    /// The `Cargo.toml` of the package or workspace to bundle, instead of the one in the current directory.
+    pub fn manifest_path(mut self, manifest_path: &Path) -> Self {
+        self.manifest_path = Some(manifest_path.to_path_buf());
+        self
+    }
+
+    /// The tool and version recorded as the generator in the metadata of the bundle.
+    pub fn generator(mut self, generator: &str) -> Self {
+        self.generator = Some(generator.to_owned());
+        self
+    }
+
     pub fn prefer(mut self, prefer: &[String]) -> Self {
         self.prefer = prefer
             .iter()
@@ -53,65 +157,199 @@ impl BundleBuilder {
         self
     }
 
-    pub fn exec(&self) -> Result<Bundle, BundleError> {
-        let loader = PackageLoader::new(&self.features)?;
+    /// Names of packages to leave out of the bundle.
+    pub fn exclude(mut self, exclude: &[String]) -> Self {
+        self.exclude = exclude.to_vec();
+        self
+    }
+
+    /// Walk the full source tree of each package looking for vendored code under undeclared licenses.
+    pub fn deep_scan(mut self, deep_scan: bool) -> Self {
+        self.deep_scan = deep_scan;
+        self
+    }
+
+    /// Additional license file name patterns to search for, see [`crate::discovery::LicenseFilePatterns::add`].
+    pub fn license_file_patterns(mut self, patterns: &[String]) -> Self {
+        for pattern in patterns {
+            self.discovery.patterns.add(pattern);
+        }
+        self
+    }
+
+    /// A local checkout of spdx/license-list-data to use for licenses without a bundled template.
+    pub fn spdx_data(mut self, spdx_data: SpdxData) -> Self {
+        self.discovery.spdx_data = Some(spdx_data);
+        self
+    }
+
+    /// Manual license overrides to apply before looking for license texts.
+    pub fn overrides(mut self, overrides: Overrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Licenses that are allowed or denied, violations are reported as diagnostics.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Names of packages to not report any diagnostics about the licenses found for, except for policy violations.
+    pub fn allow_warnings(mut self, allow_warnings: &[String]) -> Self {
+        self.allow_warnings = allow_warnings.to_vec();
+        self
+    }
+
+    /// Record the source path, confidence and match score of each license text in the bundle.
+    pub fn match_details(mut self, match_details: bool) -> Self {
+        self.match_details = match_details;
+        self
+    }
+
+    /// The number of threads to look for license files with, one per CPU by default.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Include the given package metadata, such as authors, in the bundle.
+    pub fn metadata_fields(mut self, fields: &[MetadataField]) -> Self {
+        self.metadata_fields = fields.to_vec();
+        self
+    }
+
+    /// Leave the timestamp out of the metadata of the bundle, for bundles that are embedded in a build.
+    pub fn omit_timestamp(mut self, omit_timestamp: bool) -> Self {
+        self.omit_timestamp = omit_timestamp;
+        self
+    }
+
+    /// Reuse the license texts found in registry packages by previous runs, from a cache in the target directory.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Create the bundle of the project found by `cargo metadata`, along with diagnostics about any issues with the
+    /// licenses that were found.
+    pub fn exec(&self) -> Result<(Bundle, Vec<Diagnostic>), BundleError> {
+        let loader =
+            PackageLoader::new(&self.features, &self.targets, self.manifest_path.as_deref())?;
 
         let roots = loader.get_package_roots()?;
+        let packages = loader.get_root_dependencies(&roots)?;
+        let lockfile = loader.workspace_root().join("Cargo.lock");
+
+        let mut discovery = Cow::Borrowed(&self.discovery);
+        if self.cache {
+            match DiscoveryCache::load(loader.target_directory(), &lockfile, &self.discovery) {
+                Ok(cache) => discovery.to_mut().cache = Some(Arc::new(cache)),
+                Err(err) => log::warn!("Unable to load the discovery cache: {err}"),
+            }
+        }
+        let (mut bundle, mut diagnostics) = self.run(&roots, &packages, &discovery)?;
+        bundle.workspace_relative(loader.workspace_root());
+        if let Some(cache) = &discovery.cache {
+            if let Err(err) = cache.save() {
+                log::warn!("Unable to save the discovery cache: {err}");
+            }
+        }
+
+        if lockfile.is_file() {
+            locate_in_lockfile(&mut diagnostics, &lockfile)?;
+        }
+
+        Ok((bundle, diagnostics))
+    }
+
+    /// Create the bundle of the given root packages and their dependencies, without running `cargo`.
+    ///
+    /// Roots and excluded packages are left out of `packages`. The features, targets and manifest path of the builder
+    /// are not used since the packages are already resolved, and source paths of path dependencies are kept absolute
+    /// as the workspace root is unknown.
+    pub fn exec_with_packages(
+        &self,
+        roots: &[&Package],
+        packages: &[&Package],
+    ) -> Result<(Bundle, Vec<Diagnostic>), BundleError> {
+        self.run(roots, packages, &self.discovery)
+    }
+
+    fn run(
+        &self,
+        roots: &[&Package],
+        packages: &[&Package],
+        discovery: &DiscoveryOptions,
+    ) -> Result<(Bundle, Vec<Diagnostic>), BundleError> {
         let packages = {
-            let mut packages = loader
-                .get_root_dependencies(&roots)?
-                .into_iter()
+            let mut packages = packages
+                .iter()
+                .copied()
                 .filter(|&p| !roots.iter().any(|&r| r.name == p.name))
+                .filter(|&p| !self.exclude.iter().any(|e| e == p.name.as_str()))
                 .collect::<Vec<_>>();
             packages.sort_by_key(|p| (&p.name, &p.version));
             packages
         };
 
-        // Find best possible license candidates
-        // let found_licenses: Result<Vec<FoundLicense>, FoundLicenseError> =
-        let found_licenses = packages
+        // Apply manual overrides of the license before looking for license texts
+        let overrides = packages
+            .iter()
+            .map(|&p| self.overrides.find(&p.name, &p.version))
+            .collect::<Vec<_>>();
+        let packages = packages
             .iter()
-            .map(|&p| FoundLicense::new(p))
-            .collect::<Result<Vec<FoundLicense>, FoundLicenseError>>()?;
+            .zip(&overrides)
+            .map(
+                |(&p, found)| match found.and_then(|(_, o)| o.license.as_ref()) {
+                    Some(license) => {
+                        let mut package = p.clone();
+                        package.license = Some(license.clone());
+                        Cow::Owned(package)
+                    }
+                    None => Cow::Borrowed(p),
+                },
+            )
+            .collect::<Vec<_>>();
 
-        // Write out any errors / warnings associated with each found license
-        // TODO: apply some level of warning level filters here?
-        found_licenses.iter().for_each(FoundLicense::check);
+        // Find best possible license candidates, in parallel since each package is searched independently
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(self.jobs.unwrap_or(0))
+            .build()?;
+        let found_licenses = pool.install(|| {
+            packages
+                .par_iter()
+                .map(|p| FoundLicense::new(p, discovery))
+                .collect::<Result<Vec<FoundLicense>, FoundLicenseError>>()
+        })?;
+
+        // Collect any errors / warnings associated with each found license
+        let mut diagnostics = vec![];
+        for (found_license, found) in found_licenses.iter().zip(&overrides) {
+            // Packages with overridden texts have nothing left to warn about
+            if !found.is_some_and(|(_, o)| o.covers(found_license.license())) {
+                diagnostics.extend(found_license.check());
+            }
+        }
+        if self.deep_scan {
+            let scanned: Vec<_> = pool.install(|| {
+                found_licenses
+                    .par_iter()
+                    .flat_map_iter(|found_license| found_license.check_sources(discovery))
+                    .collect()
+            });
+            diagnostics.extend(scanned);
+        }
+        diagnostics.retain(|diagnostic| !self.allow_warnings.contains(&diagnostic.package_name));
 
         // Convert to serializable licence
         let mut finalized_licenses: Vec<FinalizedLicense> =
             found_licenses.iter().map(FoundLicense::finalize).collect();
 
-        // For any Not Found check in previous to see if a license was manually added for that package-version-license combo and add it
-        if let Some(previous) = &self.previous {
-            let lookup = finalized_licenses_lookup(&previous.third_party_libraries);
-
-            for lic in &mut finalized_licenses {
-                if lic
-                    .licenses
-                    .iter()
-                    .any(|l| l.text == LICENSE_NOT_FOUNT_TEXT)
-                {
-                    let key =
-                        LicenseKey::new(lic.package_name.clone(), lic.package_version.clone());
-                    if let Some(previous_licenses) = lookup.get(&key) {
-                        for inner_license in &mut lic.licenses {
-                            if let Some(previous_license) =
-                                previous_licenses.get(inner_license.license.as_str())
-                            {
-                                if previous_license.text != LICENSE_NOT_FOUNT_TEXT {
-                                    log::info!(
-                                        "Using previous license text for {} license {}:{}",
-                                        inner_license.license,
-                                        lic.package_name,
-                                        lic.package_version
-                                    );
-                                    inner_license.text = previous_license.text.clone();
-                                }
-                            }
-                        }
-                    }
-                }
+        for (lic, found) in finalized_licenses.iter_mut().zip(&overrides) {
+            if let Some((spec, license_override)) = found {
+                Overrides::apply_texts(spec, license_override, lic)?;
             }
         }
 
@@ -133,15 +371,364 @@ impl BundleBuilder {
             }
         }
 
-        Ok(Bundle::new(&roots, finalized_licenses))
+        // For any Not Found check in previous to see if a license was manually added for that package-version-license combo and add it
+        if let Some(previous) = &self.previous {
+            self.apply_previous(previous, &mut finalized_licenses);
+        }
+        diagnostics.retain(|diagnostic| !is_resolved(diagnostic, &finalized_licenses));
+
+        for lic in &mut finalized_licenses {
+            lic.retain_metadata(&self.metadata_fields);
+        }
+        if !self.match_details {
+            for lic in &mut finalized_licenses {
+                lic.licenses
+                    .iter_mut()
+                    .for_each(LicenseAndText::omit_match_details);
+            }
+        }
+
+        let mut bundle = Bundle::new(roots, finalized_licenses);
+        bundle.metadata = Some(self.metadata(&bundle));
+        for violation in self.policy.violations(&bundle) {
+            let Some(package) = packages.iter().find(|p| {
+                p.name.as_str() == violation.package_name
+                    && p.version.to_string() == violation.package_version
+            }) else {
+                continue;
+            };
+            let message = format!(
+                "License {} of {}:{} is not allowed by the license policy",
+                violation.license, violation.package_name, violation.package_version
+            );
+            diagnostics.push(Diagnostic::new(
+                package,
+                &violation.license,
+                DiagnosticKind::PolicyViolation,
+                vec![],
+                message,
+            ));
+        }
+
+        Ok((bundle, diagnostics))
+    }
+
+    /// Describe how the bundle was produced.
+    fn metadata(&self, bundle: &Bundle) -> BundleMetadata {
+        let timestamp = (!self.omit_timestamp).then(|| self.timestamp(bundle));
+        BundleMetadata {
+            schema_version: bundle.schema().version(),
+            generator: self
+                .generator
+                .clone()
+                .unwrap_or_else(|| DEFAULT_GENERATOR.to_owned()),
+            timestamp,
+            features: self.features.clone(),
+            targets: self.targets.clone(),
+            prefer: self.prefer.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// When the bundle was produced.
+    ///
+    /// The timestamp is taken from `SOURCE_DATE_EPOCH` if set, or kept from the previous bundle if nothing changed so
+    /// regenerating an unchanged bundle gives the same output.
+    fn timestamp(&self, bundle: &Bundle) -> String {
+        let previous_timestamp = self
+            .previous
+            .as_ref()
+            .filter(|previous| bundle.diff(previous).is_empty())
+            .and_then(|previous| previous.metadata.as_ref())
+            .and_then(|metadata| metadata.timestamp.clone());
+        match source_date_epoch() {
+            Some(epoch) => rfc3339(epoch),
+            None => previous_timestamp.unwrap_or_else(|| {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                rfc3339(now as i64)
+            }),
+        }
+    }
+
+    /// Fill in licenses that still need a text with the texts of a previous bundle.
+    ///
+    /// Texts are taken from the same package version, or when carrying texts forward, from the most recent older
+    /// semver compatible version of the package if its license expression is unchanged.
+    fn apply_previous(&self, previous: &Bundle, finalized_licenses: &mut [FinalizedLicense]) {
+        let lookup = finalized_licenses_lookup(&previous.third_party_libraries);
+        let mut versions: HashMap<&str, Vec<(Version, &FinalizedLicense)>> = HashMap::new();
+        for lic in &previous.third_party_libraries {
+            if let Ok(version) = Version::parse(&lic.package_version) {
+                versions
+                    .entry(&lic.package_name)
+                    .or_default()
+                    .push((version, lic));
+            }
+        }
+        // The previous version to carry texts forward from
+        let carried_from = |lic: &FinalizedLicense| {
+            let version = Version::parse(&lic.package_version).ok()?;
+            versions
+                .get(lic.package_name.as_str())?
+                .iter()
+                .filter(|(previous, _)| previous < &version && is_compatible(previous, &version))
+                .max_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(_, previous_lic)| *previous_lic)
+        };
+
+        for lic in finalized_licenses {
+            let needs_text = lic.licenses.iter().any(LicenseAndText::needs_text);
+
+            let key = LicenseKey::new(lic.package_name.clone(), lic.package_version.clone());
+            if let Some(previous_licenses) = lookup.get(&key) {
+                for inner_license in &mut lic.licenses {
+                    if let Some(previous_license) =
+                        previous_licenses.get(inner_license.license.as_str())
+                    {
+                        // Pinned licenses always win over discovered texts
+                        if previous_license.pinned || (needs_text && !previous_license.needs_text())
+                        {
+                            log::info!(
+                                "Using previous license text for {} license {}:{}",
+                                inner_license.license,
+                                lic.package_name,
+                                lic.package_version
+                            );
+                            inner_license.apply_previous(
+                                previous_license,
+                                &lic.package_name,
+                                &lic.package_version,
+                            );
+                        }
+                    }
+                }
+            } else if self.carry_forward {
+                let Some(previous_lic) = carried_from(lic) else {
+                    continue;
+                };
+                if previous_lic.license != lic.license {
+                    continue;
+                }
+                for inner_license in &mut lic.licenses {
+                    if let Some(previous_license) = previous_lic.licenses.iter().find(|l| {
+                        l.license == inner_license.license
+                            && (l.pinned || (inner_license.needs_text() && !l.needs_text()))
+                    }) {
+                        log::info!(
+                            "Carrying forward license text for {} license from {}:{} to {}:{}",
+                            inner_license.license,
+                            previous_lic.package_name,
+                            previous_lic.package_version,
+                            lic.package_name,
+                            lic.package_version
+                        );
+                        inner_license.apply_previous(
+                            previous_license,
+                            &lic.package_name,
+                            &lic.package_version,
+                        );
+                    }
+                }
+            }
+        }
     }
 }
 
+/// Are two versions semver compatible, i.e. would `^a` and `^b` select the same releases.
+fn is_compatible(a: &Version, b: &Version) -> bool {
+    match (a.major, a.minor) {
+        (0, 0) => b.major == 0 && b.minor == 0 && a.patch == b.patch,
+        (0, minor) => b.major == 0 && b.minor == minor,
+        (major, _) => b.major == major,
+    }
+}
+
+/// Is a diagnostic about a license text resolved in the final licenses, either by a text from the previous bundle or
+/// because the license was dropped in favor of a preferred license.
+fn is_resolved(diagnostic: &Diagnostic, finalized_licenses: &[FinalizedLicense]) -> bool {
+    if !diagnostic.kind.is_about_text() {
+        return false;
+    }
+    let Some(lic) = finalized_licenses.iter().find(|lic| {
+        lic.package_name == diagnostic.package_name
+            && lic.package_version == diagnostic.package_version
+    }) else {
+        return false;
+    };
+    match lic
+        .licenses
+        .iter()
+        .find(|l| l.license == diagnostic.license)
+    {
+        Some(license) => {
+            let missing = matches!(
+                diagnostic.kind,
+                DiagnosticKind::Missing | DiagnosticKind::Canonical | DiagnosticKind::Unspecified
+            );
+            license.pinned || (missing && !license.needs_text())
+        }
+        None => true,
+    }
+}
+
+/// The `SOURCE_DATE_EPOCH` of reproducible builds, see <https://reproducible-builds.org/specs/source-date-epoch/>.
+fn source_date_epoch() -> Option<i64> {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+}
+
+/// Format seconds since the unix epoch as an RFC 3339 UTC timestamp.
+fn rfc3339(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let time = secs.rem_euclid(86400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// The packages that differ between two bundles, as `(name, version)` pairs, see [`Bundle::diff`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BundleDiff {
+    /// Packages only in the new bundle.
+    pub added: Vec<(String, String)>,
+    /// Packages only in the previous bundle.
+    pub removed: Vec<(String, String)>,
+    /// Packages in both bundles whose license or license texts changed.
+    pub changed: Vec<(String, String)>,
+}
+
+impl BundleDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The layout a [`Bundle`] is written in.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Schema {
+    /// Every license holds its own text.
+    #[default]
+    V1,
+    /// Identical texts are stored once in a `texts` table keyed by content hash, and licenses refer to them by id.
+    V2,
+}
+
+impl Schema {
+    /// The version recorded in the metadata of a bundle.
+    pub fn version(self) -> u32 {
+        match self {
+            Schema::V1 => 1,
+            Schema::V2 => 2,
+        }
+    }
+}
+
+/// How and when a bundle was produced.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleMetadata {
+    /// The version of the schema the bundle is written in, see [`Schema`].
+    pub schema_version: u32,
+    /// The tool and version that produced the bundle.
+    pub generator: String,
+    /// When the bundle was produced, as an RFC 3339 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// The additional features dependencies were pulled from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// The target triples dependencies were limited to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+    /// The preferred licenses for packages with multiple licenses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefer: Vec<String>,
+}
+
+/// A bundle as read from a file, before any text references are resolved.
+#[derive(Deserialize)]
+struct RawBundle {
+    root_name: String,
+    #[serde(default)]
+    metadata: Option<BundleMetadata>,
+    third_party_libraries: Vec<FinalizedLicense>,
+    #[serde(default)]
+    texts: BTreeMap<String, String>,
+}
+
 /// A bundle of licenses
+///
+/// Bundles read in the [`Schema::V2`] layout have their texts inlined, so they can be used as any other bundle.
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(try_from = "RawBundle")]
 pub struct Bundle {
     root_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<BundleMetadata>,
     third_party_libraries: Vec<FinalizedLicense>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    texts: BTreeMap<String, String>,
+    #[serde(skip)]
+    schema: Schema,
+}
+
+impl TryFrom<RawBundle> for Bundle {
+    type Error = BundleError;
+
+    fn try_from(raw: RawBundle) -> Result<Self, Self::Error> {
+        if let Some(metadata) = &raw.metadata {
+            if metadata.schema_version > CURRENT_SCHEMA_VERSION {
+                return Err(BundleError::UnsupportedSchema(metadata.schema_version));
+            }
+        }
+        // Bundles without metadata predate it, and only v2 bundles of that time have a texts table
+        let schema = match &raw.metadata {
+            Some(metadata) if metadata.schema_version == Schema::V2.version() => Schema::V2,
+            Some(_) => Schema::V1,
+            None if raw.texts.is_empty() => Schema::V1,
+            None => Schema::V2,
+        };
+        let mut third_party_libraries = raw.third_party_libraries;
+        for lic in &mut third_party_libraries {
+            for license in &mut lic.licenses {
+                if let Some(id) = license.text_id.take() {
+                    license.text = raw
+                        .texts
+                        .get(&id)
+                        .cloned()
+                        .ok_or(BundleError::MissingText(id))?;
+                }
+            }
+        }
+        Ok(Self {
+            root_name: raw.root_name,
+            metadata: raw.metadata,
+            third_party_libraries,
+            texts: BTreeMap::new(),
+            schema,
+        })
+    }
 }
 
 impl Bundle {
@@ -158,8 +745,103 @@ impl Bundle {
 
         Self {
             root_name: roots,
+            metadata: None,
             third_party_libraries,
+            texts: BTreeMap::new(),
+            schema: Schema::V1,
+        }
+    }
+
+    /// Make the source paths of license texts of path dependencies relative to the workspace root, so the bundle does
+    /// not depend on where the workspace is checked out.
+    fn workspace_relative(&mut self, workspace_root: &Path) {
+        let relative = |path: &mut Option<PathBuf>| {
+            if let Some(stripped) = path
+                .as_deref()
+                .and_then(|path| path.strip_prefix(workspace_root).ok())
+            {
+                *path = Some(stripped.to_path_buf());
+            }
+        };
+        for license in self
+            .third_party_libraries
+            .iter_mut()
+            .flat_map(|lic| &mut lic.licenses)
+        {
+            relative(&mut license.source_path);
+            for alternate in &mut license.alternates {
+                relative(&mut alternate.source_path);
+            }
+        }
+    }
+
+    /// The schema the bundle was read in.
+    pub fn schema(&self) -> Schema {
+        self.schema
+    }
+
+    /// How and when the bundle was produced, if known.
+    pub fn metadata(&self) -> Option<&BundleMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Add metadata to a bundle written before bundles had any, recording the tool that migrated it as the generator.
+    ///
+    /// Returns false if the bundle already has metadata.
+    pub fn migrate(&mut self, generator: &str) -> bool {
+        if self.metadata.is_some() {
+            return false;
+        }
+        self.metadata = Some(BundleMetadata {
+            schema_version: self.schema.version(),
+            generator: format!("{generator} (migrated)"),
+            timestamp: None,
+            features: vec![],
+            targets: vec![],
+            prefer: vec![],
+        });
+        true
+    }
+
+    /// The bundle laid out in the given schema for writing.
+    pub fn in_schema(&self, schema: Schema) -> Cow<'_, Self> {
+        let mut bundle = match schema {
+            Schema::V1 => Cow::Borrowed(self),
+            Schema::V2 => Cow::Owned(self.deduplicated()),
+        };
+        if self
+            .metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.schema_version != schema.version())
+        {
+            if let Some(metadata) = bundle.to_mut().metadata.as_mut() {
+                metadata.schema_version = schema.version();
+            }
+        }
+        bundle
+    }
+
+    /// Move all texts into the texts table, keyed by their hash.
+    ///
+    /// Texts that were not found are left in place so they can be filled in one by one.
+    fn deduplicated(&self) -> Self {
+        let mut bundle = self.clone();
+        for lic in &mut bundle.third_party_libraries {
+            for license in &mut lic.licenses {
+                if license.text == LICENSE_NOT_FOUNT_TEXT || license.text_id.is_some() {
+                    continue;
+                }
+                let id = text_hash(&license.text);
+                bundle
+                    .texts
+                    .entry(id.clone())
+                    .or_insert_with(|| mem::take(&mut license.text));
+                license.text.clear();
+                license.text_id = Some(id);
+            }
         }
+        bundle.schema = Schema::V2;
+        bundle
     }
 
     pub fn root_name(&self) -> &str {
@@ -170,6 +852,10 @@ impl Bundle {
         &self.third_party_libraries
     }
 
+    pub fn third_party_libraries_mut(&mut self) -> &mut [FinalizedLicense] {
+        &mut self.third_party_libraries
+    }
+
     /// Compare another [`Bundle`] against this [`Bundle`] requiring that "other" be a strict subset of self.
     pub fn check_subset(&self, other: &Self) -> bool {
         if self.root_name != other.root_name {
@@ -207,6 +893,35 @@ impl Bundle {
         }
         true
     }
+
+    /// Find the packages that were added, removed, or changed in this [`Bundle`] compared to a previous one.
+    pub fn diff(&self, previous: &Self) -> BundleDiff {
+        fn find<'a>(
+            libraries: &'a [FinalizedLicense],
+            lic: &FinalizedLicense,
+        ) -> Option<&'a FinalizedLicense> {
+            libraries.iter().find(|other| {
+                other.package_name == lic.package_name
+                    && other.package_version == lic.package_version
+            })
+        }
+        let key = |lic: &FinalizedLicense| (lic.package_name.clone(), lic.package_version.clone());
+
+        let mut diff = BundleDiff::default();
+        for lic in &self.third_party_libraries {
+            match find(&previous.third_party_libraries, lic) {
+                Some(previous_lic) if previous_lic != lic => diff.changed.push(key(lic)),
+                Some(_) => (),
+                None => diff.added.push(key(lic)),
+            }
+        }
+        for lic in &previous.third_party_libraries {
+            if find(&self.third_party_libraries, lic).is_none() {
+                diff.removed.push(key(lic));
+            }
+        }
+        diff
+    }
 }
 
 impl PartialEq for Bundle {
@@ -227,3 +942,510 @@ impl PartialEq for Bundle {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::Config,
+        discovery::Confidence,
+        format::Format,
+        test_util::{package, TempDir},
+    };
+
+    fn library(name: &str, version: &str, text: &str) -> FinalizedLicense {
+        FinalizedLicense {
+            package_name: name.to_owned(),
+            package_version: version.to_owned(),
+            repository: String::new(),
+            source: None,
+            authors: vec![],
+            homepage: None,
+            description: None,
+            license: "MIT".to_owned(),
+            licenses: vec![LicenseAndText::new(&License::MIT, text.to_owned())],
+            license_override: None,
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let previous = Bundle {
+            root_name: "root".to_owned(),
+            third_party_libraries: vec![
+                library("a", "1.0.0", "a"),
+                library("b", "1.0.0", "b"),
+                library("c", "1.0.0", "c"),
+            ],
+            texts: BTreeMap::new(),
+            schema: Schema::V1,
+            metadata: None,
+        };
+        let bundle = Bundle {
+            root_name: "root".to_owned(),
+            third_party_libraries: vec![
+                library("a", "1.0.0", "a"),
+                library("b", "1.0.0", "changed"),
+                library("c", "1.1.0", "c"),
+            ],
+            texts: BTreeMap::new(),
+            schema: Schema::V1,
+            metadata: None,
+        };
+
+        let diff = bundle.diff(&previous);
+        assert_eq!(diff.added, vec![("c".to_owned(), "1.1.0".to_owned())]);
+        assert_eq!(diff.removed, vec![("c".to_owned(), "1.0.0".to_owned())]);
+        assert_eq!(diff.changed, vec![("b".to_owned(), "1.0.0".to_owned())]);
+        assert!(bundle.diff(&bundle).is_empty());
+    }
+
+    #[test]
+    fn deduplicated_round_trip() {
+        let bundle = Bundle {
+            root_name: "root".to_owned(),
+            third_party_libraries: vec![
+                library("a", "1.0.0", "MIT text"),
+                library("b", "1.0.0", "MIT text"),
+                library("c", "1.0.0", LICENSE_NOT_FOUNT_TEXT),
+            ],
+            texts: BTreeMap::new(),
+            schema: Schema::V1,
+            metadata: None,
+        };
+
+        let deduplicated = bundle.in_schema(Schema::V2);
+        assert_eq!(deduplicated.texts.len(), 1);
+        assert_eq!(
+            deduplicated.third_party_libraries[2].licenses[0].text,
+            LICENSE_NOT_FOUNT_TEXT
+        );
+
+        for format in [Format::Json, Format::Toml, Format::Yaml] {
+            let mut buffer = vec![];
+            format
+                .serialize_to_writer(&mut buffer, &deduplicated)
+                .unwrap();
+            let read = format.deserialize_from_reader(&buffer[..]).unwrap();
+            assert_eq!(read.schema(), Schema::V2);
+            assert!(read.texts.is_empty());
+            assert_eq!(read, bundle);
+            assert!(read.check_subset(&bundle));
+        }
+    }
+
+    #[test]
+    fn empty_texts_round_trip() {
+        let mut bundle = Bundle::new(&[], vec![library("a", "1.0.0", LICENSE_NOT_FOUNT_TEXT)]);
+        bundle.migrate("test");
+        let v2 = bundle.in_schema(Schema::V2);
+        assert!(v2.texts.is_empty());
+
+        for format in [Format::Json, Format::Toml, Format::Yaml] {
+            let mut buffer = vec![];
+            format.serialize_to_writer(&mut buffer, &v2).unwrap();
+            let read = format.deserialize_from_reader(&buffer[..]).unwrap();
+            assert_eq!(read.schema(), Schema::V2);
+            assert_eq!(read.metadata().unwrap().schema_version, 2);
+        }
+    }
+
+    #[test]
+    fn missing_text_id() {
+        let json = r#"{
+            "root_name": "root",
+            "third_party_libraries": [{
+                "package_name": "a",
+                "package_version": "1.0.0",
+                "license": "MIT",
+                "licenses": [{ "license": "MIT", "text_id": "unknown" }]
+            }],
+            "texts": { "other": "MIT text" }
+        }"#;
+        assert!(Format::Json
+            .deserialize_from_reader(json.as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn apply_previous() {
+        let previous = Bundle::new(
+            &[],
+            vec![
+                library("same", "1.0.0", "same text"),
+                library("upgraded", "1.0.0", "old upgraded text"),
+                library("upgraded", "1.1.0", "upgraded text"),
+                library("upgraded", "2.0.0", "next major text"),
+                library("breaking", "1.0.0", "breaking text"),
+                library("downgraded", "1.5.0", "downgraded text"),
+                library("unstable", "0.3.1", "unstable text"),
+                library("relicensed", "1.0.0", "relicensed text"),
+            ],
+        );
+        let libraries = || {
+            let mut relicensed = library("relicensed", "1.0.1", LICENSE_NOT_FOUNT_TEXT);
+            relicensed.license = "MIT OR Apache-2.0".to_owned();
+            vec![
+                library("same", "1.0.0", LICENSE_NOT_FOUNT_TEXT),
+                library("upgraded", "1.2.0", LICENSE_NOT_FOUNT_TEXT),
+                library("breaking", "2.0.0", LICENSE_NOT_FOUNT_TEXT),
+                library("downgraded", "1.2.0", LICENSE_NOT_FOUNT_TEXT),
+                library("unstable", "0.4.0", LICENSE_NOT_FOUNT_TEXT),
+                relicensed,
+            ]
+        };
+        let texts = |libraries: &[FinalizedLicense]| -> Vec<String> {
+            libraries
+                .iter()
+                .map(|lic| lic.licenses[0].text.clone())
+                .collect()
+        };
+
+        let mut exact = libraries();
+        BundleBuilder::new().apply_previous(&previous, &mut exact);
+        assert_eq!(
+            texts(&exact),
+            [
+                "same text",
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT
+            ]
+        );
+
+        // Texts are only carried forward from the latest older compatible version
+        let mut carried = libraries();
+        BundleBuilder::new()
+            .carry_forward(true)
+            .apply_previous(&previous, &mut carried);
+        assert_eq!(
+            texts(&carried),
+            [
+                "same text",
+                "upgraded text",
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT,
+                LICENSE_NOT_FOUNT_TEXT
+            ]
+        );
+    }
+
+    #[test]
+    fn compatible_versions() {
+        let compatible = |a: &str, b: &str| {
+            is_compatible(&Version::parse(a).unwrap(), &Version::parse(b).unwrap())
+        };
+        assert!(compatible("1.0.0", "1.9.3"));
+        assert!(!compatible("1.0.0", "2.0.0"));
+        assert!(compatible("0.3.1", "0.3.4"));
+        assert!(!compatible("0.3.1", "0.4.0"));
+        assert!(!compatible("0.0.1", "0.0.2"));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(rfc3339(-1), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn unsupported_schema() {
+        let json = r#"{
+            "root_name": "root",
+            "metadata": { "schema_version": 99, "generator": "future" },
+            "third_party_libraries": []
+        }"#;
+        assert!(matches!(
+            serde_json::from_str::<Bundle>(json),
+            Err(err) if err.to_string().contains("99")
+        ));
+    }
+
+    #[test]
+    fn exec_with_packages() {
+        let dir = TempDir::new("exec");
+        std::fs::create_dir_all(dir.join("with-file")).unwrap();
+        std::fs::write(dir.join("with-file").join("Cargo.toml"), "").unwrap();
+        std::fs::write(dir.join("with-file").join("LICENSE-MIT"), "MIT license").unwrap();
+
+        let root = package("root", "MIT", &dir);
+        let with_file = package("with-file", "MIT OR Apache-2.0", &dir);
+        let without_file = package("without-file", "MIT", &dir);
+        let excluded = package("excluded", "MIT", &dir);
+
+        let (bundle, diagnostics) = BundleBuilder::new()
+            .prefer(&["MIT".to_owned()])
+            .exclude(&["excluded".to_owned()])
+            .exec_with_packages(&[&root], &[&root, &without_file, &with_file, &excluded])
+            .unwrap();
+
+        assert_eq!(bundle.root_name(), "root");
+        assert_eq!(bundle.metadata().unwrap().schema_version, 1);
+        assert_eq!(
+            bundle
+                .in_schema(Schema::V2)
+                .metadata()
+                .unwrap()
+                .schema_version,
+            2
+        );
+        let libs = bundle.third_party_libraries();
+        assert_eq!(libs.len(), 2);
+        assert_eq!(libs[0].package_name, "with-file");
+        assert_eq!(libs[0].license, "MIT");
+        assert_eq!(libs[0].licenses[0].text, "MIT license");
+        assert_eq!(libs[1].package_name, "without-file");
+        assert_eq!(libs[1].licenses[0].text, LICENSE_NOT_FOUNT_TEXT);
+
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.package_name == "without-file" && d.kind == DiagnosticKind::Missing));
+        assert!(diagnostics.iter().all(|d| d.lockfile.is_none()));
+    }
+
+    #[test]
+    fn license_mismatch() {
+        let dir = TempDir::new("mismatch");
+        let package_dir = dir.join("mislabeled");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(package_dir.join("Cargo.toml"), "").unwrap();
+        std::fs::write(
+            package_dir.join("LICENSE"),
+            License::Apache_2_0.template().unwrap(),
+        )
+        .unwrap();
+
+        let root = package("root", "MIT", &dir);
+        let mislabeled = package("mislabeled", "MIT", &dir);
+        let (_, diagnostics) = BundleBuilder::new()
+            .exec_with_packages(&[&root], &[&mislabeled])
+            .unwrap();
+
+        let mismatch = diagnostics
+            .iter()
+            .find(|d| d.kind == DiagnosticKind::LicenseMismatch)
+            .unwrap();
+        assert_eq!(mismatch.license, "MIT");
+        assert_eq!(mismatch.paths, vec![package_dir.join("LICENSE")]);
+        assert!(mismatch.message.contains("looks like Apache-2.0"));
+    }
+
+    #[test]
+    fn invalid_reuse() {
+        let dir = TempDir::new("reuse");
+        let package_dir = dir.join("broken-reuse");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(package_dir.join("Cargo.toml"), "").unwrap();
+        std::fs::write(package_dir.join("REUSE.toml"), "[[annotations]\n").unwrap();
+        std::fs::write(
+            package_dir.join("LICENSE-MIT"),
+            License::MIT.template().unwrap(),
+        )
+        .unwrap();
+
+        let root = package("root", "MIT", &dir);
+        let broken = package("broken-reuse", "MIT", &dir);
+        let (bundle, diagnostics) = BundleBuilder::new()
+            .exec_with_packages(&[&root], &[&broken])
+            .unwrap();
+
+        assert_eq!(
+            bundle.third_party_libraries[0].licenses[0].text,
+            License::MIT.template().unwrap()
+        );
+        let invalid = diagnostics
+            .iter()
+            .find(|d| d.kind == DiagnosticKind::InvalidReuse)
+            .unwrap();
+        assert_eq!(invalid.paths, vec![package_dir]);
+        assert!(invalid.message.contains("REUSE.toml"));
+    }
+
+    #[test]
+    fn multiple_unsure_files_fail() {
+        let dir = TempDir::new("multiple");
+        let package_dir = dir.join("two-files");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(package_dir.join("Cargo.toml"), "").unwrap();
+        std::fs::write(package_dir.join("LICENSE-MIT"), "not quite a license").unwrap();
+        std::fs::write(package_dir.join("LICENCE-MIT"), "not quite a license").unwrap();
+
+        let root = package("root", "MIT", &dir);
+        let two_files = package("two-files", "MIT", &dir);
+        let (_, diagnostics) = BundleBuilder::new()
+            .exec_with_packages(&[&root], &[&two_files])
+            .unwrap();
+
+        let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind).collect();
+        assert_eq!(
+            kinds,
+            [DiagnosticKind::MultipleFiles, DiagnosticKind::Unsure]
+        );
+
+        // More files must not get past the gates a single unsure file fails
+        let fail_on = Config {
+            fail_on: vec![DiagnosticKind::Unsure],
+            ..Config::default()
+        };
+        let min_confidence = Config {
+            min_confidence: Some(Confidence::MultiplePossibleLicenseFiles),
+            ..Config::default()
+        };
+        for config in [fail_on, min_confidence] {
+            assert!(diagnostics.iter().any(|d| config.fails_on(d)));
+        }
+    }
+
+    #[test]
+    fn resolved_diagnostics() {
+        let diagnostic = |name: &str, license: &str, kind| Diagnostic {
+            package_name: name.to_owned(),
+            package_version: "1.0.0".to_owned(),
+            license: license.to_owned(),
+            kind,
+            paths: vec![],
+            manifest_path: PathBuf::from("Cargo.toml"),
+            lockfile: None,
+            message: String::new(),
+        };
+        let mut pinned = library("pinned", "1.0.0", "pinned text");
+        pinned.licenses[0].pinned = true;
+        let libraries = vec![
+            library("filled", "1.0.0", "text from previous"),
+            library("missing", "1.0.0", LICENSE_NOT_FOUNT_TEXT),
+            pinned,
+        ];
+
+        // A missing text filled in from the previous bundle
+        assert!(is_resolved(
+            &diagnostic("filled", "MIT", DiagnosticKind::Missing),
+            &libraries
+        ));
+        assert!(!is_resolved(
+            &diagnostic("missing", "MIT", DiagnosticKind::Missing),
+            &libraries
+        ));
+        // A low confidence text is only resolved by pinning it
+        assert!(!is_resolved(
+            &diagnostic("filled", "MIT", DiagnosticKind::Unsure),
+            &libraries
+        ));
+        assert!(is_resolved(
+            &diagnostic("pinned", "MIT", DiagnosticKind::Unsure),
+            &libraries
+        ));
+        // A license dropped in favor of a preferred license
+        assert!(is_resolved(
+            &diagnostic("filled", "Apache-2.0", DiagnosticKind::Missing),
+            &libraries
+        ));
+        // Diagnostics not about the text, or about packages not in the bundle
+        assert!(!is_resolved(
+            &diagnostic("pinned", "MIT", DiagnosticKind::PolicyViolation),
+            &libraries
+        ));
+        assert!(!is_resolved(
+            &diagnostic("excluded", "MIT", DiagnosticKind::Missing),
+            &libraries
+        ));
+    }
+
+    #[test]
+    fn match_details() {
+        let dir = TempDir::new("details");
+        let package_dir = dir.join("dep");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(package_dir.join("Cargo.toml"), "").unwrap();
+        std::fs::write(
+            package_dir.join("LICENSE-MIT"),
+            License::MIT.template().unwrap(),
+        )
+        .unwrap();
+        let root = package("root", "MIT", &dir);
+        let dep = package("dep", "MIT", &dir);
+
+        let (bundle, _) = BundleBuilder::new()
+            .exec_with_packages(&[&root], &[&dep])
+            .unwrap();
+        let license = &bundle.third_party_libraries[0].licenses[0];
+        assert_eq!(license.source_path, None);
+        assert_eq!(license.confidence, None);
+        assert_eq!(license.match_score, None);
+
+        let (mut bundle, _) = BundleBuilder::new()
+            .match_details(true)
+            .exec_with_packages(&[&root], &[&dep])
+            .unwrap();
+        let license = &bundle.third_party_libraries[0].licenses[0];
+        assert_eq!(license.source_path, Some(package_dir.join("LICENSE-MIT")));
+        assert_eq!(license.confidence, Some(Confidence::Confident));
+        assert_eq!(license.match_score, Some(1.0));
+
+        bundle.workspace_relative(&dir);
+        assert_eq!(
+            bundle.third_party_libraries[0].licenses[0].source_path,
+            Some(PathBuf::from("dep/LICENSE-MIT"))
+        );
+    }
+
+    #[test]
+    fn migrate() {
+        let v1 = r#"{
+            "root_name": "root",
+            "third_party_libraries": [{
+                "package_name": "a",
+                "package_version": "1.0.0",
+                "license": "MIT",
+                "licenses": [{ "license": "MIT", "text": "MIT text" }]
+            }]
+        }"#;
+        let mut bundle = Format::Json.deserialize_from_reader(v1.as_bytes()).unwrap();
+        assert!(bundle.metadata().is_none());
+
+        assert!(bundle.migrate("tool 1.0"));
+        let metadata = bundle.metadata().unwrap().clone();
+        assert_eq!(metadata.schema_version, 1);
+        assert_eq!(metadata.generator, "tool 1.0 (migrated)");
+        assert_eq!(metadata.timestamp, None);
+
+        // Migrating again leaves the bundle as is
+        assert!(!bundle.migrate("tool 2.0"));
+        assert_eq!(bundle.metadata(), Some(&metadata));
+
+        let v2 = bundle.in_schema(Schema::V2);
+        assert_eq!(v2.metadata().unwrap().schema_version, 2);
+        assert_eq!(v2.metadata().unwrap().generator, metadata.generator);
+        let mut buffer = vec![];
+        Format::Json.serialize_to_writer(&mut buffer, &v2).unwrap();
+        let read = Format::Json.deserialize_from_reader(&buffer[..]).unwrap();
+        assert_eq!(read.schema(), Schema::V2);
+        assert_eq!(read.metadata().unwrap().schema_version, 2);
+        assert_eq!(
+            read.in_schema(Schema::V1)
+                .metadata()
+                .unwrap()
+                .schema_version,
+            1
+        );
+    }
+
+    #[test]
+    fn omit_timestamp() {
+        let root = package("root", "MIT", Path::new("/workspace"));
+        let (bundle, _) = BundleBuilder::new()
+            .exec_with_packages(&[&root], &[])
+            .unwrap();
+        assert!(bundle.metadata().unwrap().timestamp.is_some());
+
+        let (bundle, _) = BundleBuilder::new()
+            .omit_timestamp(true)
+            .exec_with_packages(&[&root], &[])
+            .unwrap();
+        assert_eq!(bundle.metadata().unwrap().timestamp, None);
+    }
+}
@@ -0,0 +1,278 @@
+//! A cache of the license texts discovered in registry packages, which never change once published.
+//!
+//! Entries are keyed by package id, the checksum of the package in `Cargo.lock` and the license looked for. Packages
+//! without a checksum, i.e. path and git dependencies, are never cached.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use cargo_metadata::Package;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    discovery::{DiscoveryOptions, LicenseText},
+    license::License,
+    spdx_data::SpdxData,
+};
+
+/// The location of the cache file in the target directory.
+const CACHE_FILE: &str = "bundle-licenses/discovery-cache.json";
+/// Caches written by any other version are discarded.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Unable to parse Cargo.lock: {0}")]
+    Lockfile(#[from] toml::de::Error),
+}
+
+type Entries = BTreeMap<String, Vec<LicenseText>>;
+
+#[derive(Deserialize, Serialize)]
+struct CacheFile {
+    version: String,
+    /// The discovery options the texts were found with.
+    options: String,
+    entries: Entries,
+}
+
+/// The license texts found in each registry package by previous runs.
+#[derive(Debug)]
+pub struct DiscoveryCache {
+    path: PathBuf,
+    options: String,
+    checksums: HashMap<(String, String), String>,
+    /// The entries read from disk.
+    previous: Entries,
+    /// The entries used or found in this run, which replace the previous entries when saved.
+    entries: Mutex<Entries>,
+}
+
+impl DiscoveryCache {
+    /// Load the cache in `target_dir`, for the packages with a checksum in `lockfile`.
+    ///
+    /// A cache written by another version of this crate or with other discovery options is discarded.
+    pub fn load(
+        target_dir: &Path,
+        lockfile: &Path,
+        options: &DiscoveryOptions,
+    ) -> Result<Self, CacheError> {
+        let checksums = lockfile_checksums(&fs::read_to_string(lockfile)?)?;
+
+        let path = target_dir.join(CACHE_FILE);
+        let options = fingerprint(options);
+        let previous = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice::<CacheFile>(&contents)
+                .ok()
+                .filter(|file| file.version == VERSION && file.options == options)
+                .map(|file| file.entries)
+                .unwrap_or_default(),
+            Err(err) if err.kind() == ErrorKind::NotFound => Entries::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            path,
+            options,
+            checksums,
+            previous,
+            entries: Mutex::new(Entries::new()),
+        })
+    }
+
+    fn key(&self, package: &Package, license: &License) -> Option<String> {
+        let checksum = self
+            .checksums
+            .get(&(package.name.to_string(), package.version.to_string()))?;
+        Some(format!("{} {} {}", package.id, checksum, license))
+    }
+
+    /// The texts found for a license of a package by a previous run.
+    pub fn get(&self, package: &Package, license: &License) -> Option<Vec<LicenseText>> {
+        let key = self.key(package, license)?;
+        let texts = self.previous.get(&key)?.clone();
+        self.entries.lock().unwrap().insert(key, texts.clone());
+        Some(texts)
+    }
+
+    /// Remember the texts found for a license of a package, if the package can be cached.
+    pub fn insert(&self, package: &Package, license: &License, texts: &[LicenseText]) {
+        if let Some(key) = self.key(package, license) {
+            self.entries.lock().unwrap().insert(key, texts.to_vec());
+        }
+    }
+
+    /// Write the entries of this run to disk if they changed, dropping entries of packages that are no longer used.
+    pub fn save(&self) -> Result<(), CacheError> {
+        let entries = self.entries.lock().unwrap();
+        if *entries == self.previous {
+            return Ok(());
+        }
+
+        let file = CacheFile {
+            version: VERSION.to_owned(),
+            options: self.options.clone(),
+            entries: entries.clone(),
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file first so concurrent runs never read a partial cache
+        let tmp = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        fs::rename(&tmp, &self.path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })?;
+        Ok(())
+    }
+}
+
+/// The options that change which texts are found.
+fn fingerprint(options: &DiscoveryOptions) -> String {
+    format!(
+        "{:?} {:?}",
+        options.patterns,
+        options.spdx_data.as_ref().map(SpdxData::fingerprint)
+    )
+}
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    checksum: Option<String>,
+}
+
+/// Map the name and version of each package in a `Cargo.lock` file to its checksum, if it has one.
+fn lockfile_checksums(contents: &str) -> Result<HashMap<(String, String), String>, CacheError> {
+    let lockfile: Lockfile = toml::from_str(contents)?;
+    Ok(lockfile
+        .package
+        .into_iter()
+        .filter_map(|package| Some(((package.name, package.version), package.checksum?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        discovery::Confidence,
+        spdx_data::LICENSE_LIST,
+        test_util::{package, TempDir},
+    };
+
+    #[test]
+    fn checksums() {
+        let contents = r#"version = 4
+
+[[package]]
+name = "anyhow"
+version = "1.0.100"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "a23eb6b1614318a8071c9b2521f36b424b2c83db5eb3a0fead4a6c0809af6e61"
+
+[[package]]
+name = "local"
+version = "0.1.0"
+dependencies = [
+ "anyhow",
+]
+"#;
+        let checksums = lockfile_checksums(contents).unwrap();
+        assert_eq!(checksums.len(), 1);
+        assert_eq!(
+            checksums[&("anyhow".to_owned(), "1.0.100".to_owned())],
+            "a23eb6b1614318a8071c9b2521f36b424b2c83db5eb3a0fead4a6c0809af6e61"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new("cache");
+        let spdx_dir = dir.join("spdx");
+        fs::create_dir_all(spdx_dir.join("text")).unwrap();
+        fs::create_dir_all(spdx_dir.join("json")).unwrap();
+        fs::write(
+            spdx_dir.join(LICENSE_LIST),
+            r#"{"licenseListVersion": "3.26"}"#,
+        )
+        .unwrap();
+        let lockfile = dir.join("Cargo.lock");
+        fs::write(
+            &lockfile,
+            "[[package]]\nname = \"cached\"\nversion = \"1.0.0\"\nchecksum = \"abc\"\n",
+        )
+        .unwrap();
+
+        let options = DiscoveryOptions {
+            spdx_data: Some(SpdxData::new(&spdx_dir).unwrap()),
+            ..DiscoveryOptions::default()
+        };
+        let cached = package("cached", "MIT", &dir);
+        let local = package("local", "MIT", &dir);
+        let texts = vec![LicenseText {
+            path: PathBuf::from("LICENSE"),
+            text: "Apache license".to_owned(),
+            confidence: Confidence::Unsure,
+            match_score: Some(0.5),
+            identified: Some("Apache-2.0".to_owned()),
+        }];
+
+        let cache = DiscoveryCache::load(&dir, &lockfile, &options).unwrap();
+        assert_eq!(cache.get(&cached, &License::MIT), None);
+        cache.insert(&cached, &License::MIT, &texts);
+        cache.insert(&local, &License::MIT, &texts);
+        cache.save().unwrap();
+
+        let cache = DiscoveryCache::load(&dir, &lockfile, &options).unwrap();
+        assert_eq!(cache.get(&cached, &License::MIT), Some(texts.clone()));
+        assert_eq!(cache.get(&cached, &License::Apache_2_0), None);
+        assert_eq!(cache.get(&local, &License::MIT), None);
+
+        // Other patterns discard the cache
+        let mut patterns = options.clone();
+        patterns.patterns.add("NOTICE");
+        let cache = DiscoveryCache::load(&dir, &lockfile, &patterns).unwrap();
+        assert_eq!(cache.get(&cached, &License::MIT), None);
+
+        // So does another release of the SPDX data
+        fs::write(
+            spdx_dir.join(LICENSE_LIST),
+            r#"{"licenseListVersion": "3.27"}"#,
+        )
+        .unwrap();
+        let cache = DiscoveryCache::load(&dir, &lockfile, &options).unwrap();
+        assert_eq!(cache.get(&cached, &License::MIT), None);
+        fs::write(
+            spdx_dir.join(LICENSE_LIST),
+            r#"{"licenseListVersion": "3.26"}"#,
+        )
+        .unwrap();
+
+        // And a cache written by another version
+        let path = dir.join(CACHE_FILE);
+        let mut file: CacheFile = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        file.version = "0.0.0".to_owned();
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+        let cache = DiscoveryCache::load(&dir, &lockfile, &options).unwrap();
+        assert_eq!(cache.get(&cached, &License::MIT), None);
+    }
+}
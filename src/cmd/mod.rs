@@ -0,0 +1,3 @@
+//! Interactive subcommands that edit an existing thirdparty file.
+pub mod resolve;
+pub mod review;
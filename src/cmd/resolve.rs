@@ -0,0 +1,261 @@
+//! Fill in licenses whose text could not be found from a local file, a license template, or pasted text.
+use std::{
+    fs,
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use bundle_licenses_lib::{
+    bundle::Bundle,
+    discovery::DiscoveryOptions,
+    finalized_license::{LicenseAndText, LICENSE_NOT_FOUNT_TEXT},
+    license::License,
+};
+
+/// The line that ends pasted text.
+const END_OF_PASTE: &str = ".";
+
+/// Read a line from `input`, or None at the end of input.
+fn read_line<R: BufRead>(input: &mut R) -> Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned()))
+}
+
+fn prompt<R: BufRead, W: Write>(
+    input: &mut R,
+    out: &mut W,
+    prompt: &str,
+) -> Result<Option<String>> {
+    write!(out, "{prompt}")?;
+    out.flush()?;
+    read_line(input)
+}
+
+/// Read pasted lines until a line with only `.` or the end of input.
+fn read_paste<R: BufRead>(input: &mut R) -> Result<String> {
+    let mut text = String::new();
+    while let Some(line) = read_line(input)? {
+        if line == END_OF_PASTE {
+            break;
+        }
+        text.push_str(&line);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// What to do with a license without a text.
+enum Answer {
+    Text(String),
+    Skip,
+    Quit,
+}
+
+/// Ask how to resolve a license until a usable answer is given.
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    out: &mut W,
+    license: &LicenseAndText,
+    options: &DiscoveryOptions,
+) -> Result<Answer> {
+    loop {
+        let Some(answer) = prompt(
+            input,
+            out,
+            "Resolve with a (f)ile, the license (t)emplate, (p)asted text, (s)kip or (q)uit: ",
+        )?
+        else {
+            return Ok(Answer::Quit);
+        };
+        match answer.trim() {
+            "f" => {
+                let Some(path) = prompt(input, out, "Path of the license file: ")? else {
+                    return Ok(Answer::Quit);
+                };
+                match fs::read_to_string(path.trim()) {
+                    Ok(text) => return Ok(Answer::Text(text)),
+                    Err(err) => writeln!(out, "Unable to read {}: {}", path.trim(), err)?,
+                }
+            }
+            "t" => {
+                let template = License::from_str(&license.license)
+                    .ok()
+                    .and_then(|lic| options.template(&lic));
+                match template {
+                    Some(template) => {
+                        writeln!(
+                            out,
+                            "Using the template, fill in any placeholders such as the copyright holder afterwards"
+                        )?;
+                        return Ok(Answer::Text(template.into_owned()));
+                    }
+                    None => writeln!(
+                        out,
+                        "No template for {} license, pass --spdx-data for more templates",
+                        license.license
+                    )?,
+                }
+            }
+            "p" => {
+                writeln!(
+                    out,
+                    "Paste the license text, end it with a line containing only {END_OF_PASTE:?}:"
+                )?;
+                let text = read_paste(input)?;
+                if text.trim().is_empty() {
+                    writeln!(out, "No text was pasted")?;
+                } else {
+                    return Ok(Answer::Text(text));
+                }
+            }
+            "s" => return Ok(Answer::Skip),
+            "q" => return Ok(Answer::Quit),
+            answer => writeln!(out, "Unknown choice {answer:?}")?,
+        }
+    }
+}
+
+/// Walk every license without a text and fill in the text given on `input`.
+///
+/// Returns the number of licenses that were resolved.
+pub fn resolve<R: BufRead, W: Write>(
+    bundle: &mut Bundle,
+    options: &DiscoveryOptions,
+    mut input: R,
+    mut out: W,
+) -> Result<usize> {
+    let mut resolved = 0;
+    for lib in bundle.third_party_libraries_mut() {
+        for license in &mut lib.licenses {
+            if !license.needs_text() {
+                continue;
+            }
+
+            let what = if license.text == LICENSE_NOT_FOUNT_TEXT {
+                "has no text"
+            } else {
+                "uses the canonical SPDX text"
+            };
+            writeln!(
+                out,
+                "\n{}:{} - {} license {}",
+                lib.package_name, lib.package_version, license.license, what
+            )?;
+            if !lib.repository.is_empty() {
+                writeln!(out, "Repository: {}", lib.repository)?;
+            }
+
+            match ask(&mut input, &mut out, license, options)? {
+                Answer::Text(text) => {
+                    license.text = text;
+                    license.canonical = false;
+                    license.clear_match_details();
+                    resolved += 1;
+                }
+                Answer::Skip => (),
+                Answer::Quit => return Ok(resolved),
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use bundle_licenses_lib::format::Format;
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn resolve_from_file_template_and_paste() {
+        let yaml = r#"
+root_name: root
+third_party_libraries:
+- package_name: from-file
+  package_version: 1.0.0
+  repository: https://example.com/from-file
+  license: Custom-License
+  licenses:
+  - license: Custom-License
+    text: NOT FOUND
+- package_name: from-template
+  package_version: 1.0.0
+  license: MIT
+  licenses:
+  - license: MIT
+    text: NOT FOUND
+- package_name: pasted
+  package_version: 1.0.0
+  license: ISC
+  licenses:
+  - license: ISC
+    text: ISC canonical text
+    canonical: true
+- package_name: skipped
+  package_version: 1.0.0
+  license: Zlib
+  licenses:
+  - license: Zlib
+    text: NOT FOUND
+- package_name: found
+  package_version: 1.0.0
+  license: MIT
+  licenses:
+  - license: MIT
+    text: found
+"#;
+        let mut bundle = Format::Yaml
+            .deserialize_from_reader(yaml.as_bytes())
+            .unwrap();
+        let dir = TempDir::new("resolve");
+        let file = dir.join("LICENSE");
+        fs::write(&file, "custom license text\n").unwrap();
+
+        let input = format!(
+            "f\n{}.missing\nf\n{}\nt\np\n.\np\nISC license\n  indented\n.\ns\n",
+            file.display(),
+            file.display()
+        );
+        let mut out = vec![];
+        let resolved = resolve(
+            &mut bundle,
+            &DiscoveryOptions::default(),
+            Cursor::new(input),
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(resolved, 3);
+        assert!(out.contains("from-file:1.0.0 - Custom-License license has no text"));
+        assert!(out.contains("Repository: https://example.com/from-file"));
+        assert!(out.contains("Unable to read"));
+        assert!(out.contains("pasted:1.0.0 - ISC license uses the canonical SPDX text"));
+        assert!(out.contains("No text was pasted"));
+        assert!(!out.contains("found:1.0.0"));
+
+        let texts: Vec<_> = bundle
+            .third_party_libraries()
+            .iter()
+            .map(|lib| lib.licenses[0].text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "custom license text\n",
+                License::MIT.template().unwrap(),
+                "ISC license\n  indented\n",
+                LICENSE_NOT_FOUNT_TEXT,
+                "found",
+            ]
+        );
+        assert!(!bundle.third_party_libraries()[2].licenses[0].canonical);
+    }
+}
@@ -0,0 +1,248 @@
+//! Review licenses that matched several files equally well and pin the right one.
+use std::{
+    env,
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use bundle_licenses_lib::{bundle::Bundle, finalized_license::LicenseAndText};
+
+/// Width used when `$COLUMNS` is not set.
+const DEFAULT_WIDTH: usize = 120;
+/// Candidates are shown one after another when columns would be narrower than this.
+const MIN_COLUMN_WIDTH: usize = 30;
+const COLUMN_SEPARATOR: &str = " | ";
+
+/// A license file that could be chosen for a license.
+struct Candidate {
+    source_path: Option<PathBuf>,
+    match_score: Option<f32>,
+    text: String,
+}
+
+impl Candidate {
+    fn all(license: &LicenseAndText) -> Vec<Self> {
+        let mut candidates = vec![Self {
+            source_path: license.source_path.clone(),
+            match_score: license.match_score,
+            text: license.text.clone(),
+        }];
+        candidates.extend(license.alternates.iter().map(|alternate| Self {
+            source_path: alternate.source_path.clone(),
+            match_score: alternate.match_score,
+            text: alternate.text.clone(),
+        }));
+        candidates
+    }
+
+    fn header(&self, index: usize) -> String {
+        let path = self
+            .source_path
+            .as_ref()
+            .map_or_else(|| String::from("unknown file"), |p| p.display().to_string());
+        match self.match_score {
+            Some(score) => format!("[{}] {} (score {})", index + 1, path, score),
+            None => format!("[{}] {}", index + 1, path),
+        }
+    }
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Split text into lines of at most `width` characters.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for line in text.replace('\t', "    ").lines() {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        for chunk in chars.chunks(width) {
+            lines.push(chunk.iter().collect());
+        }
+    }
+    lines
+}
+
+/// Print the candidates next to each other, or one after another if the terminal is too narrow.
+fn print_candidates<W: Write>(out: &mut W, candidates: &[Candidate]) -> Result<()> {
+    let count = candidates.len();
+    let width = (terminal_width().saturating_sub(COLUMN_SEPARATOR.len() * (count - 1))) / count;
+
+    if width < MIN_COLUMN_WIDTH {
+        for (i, candidate) in candidates.iter().enumerate() {
+            writeln!(out, "{}", candidate.header(i))?;
+            writeln!(out, "{}", candidate.text)?;
+        }
+        return Ok(());
+    }
+
+    let columns: Vec<Vec<String>> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            let mut column = wrap(&candidate.header(i), width);
+            column.push("-".repeat(width));
+            column.extend(wrap(&candidate.text, width));
+            column
+        })
+        .collect();
+    let rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+    for row in 0..rows {
+        let line = columns
+            .iter()
+            .map(|column| {
+                let cell = column.get(row).map_or("", String::as_str);
+                format!("{cell:<width$}")
+            })
+            .collect::<Vec<_>>()
+            .join(COLUMN_SEPARATOR);
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Walk every license with alternate texts, and pin the candidate chosen on `input`.
+///
+/// Pinned licenses only have an alternate when their upstream text changed since they were pinned, pinning either
+/// text acknowledges the change. Returns the number of licenses that were pinned.
+pub fn review<R: BufRead, W: Write>(
+    bundle: &mut Bundle,
+    mut input: R,
+    mut out: W,
+) -> Result<usize> {
+    let mut pinned = 0;
+    for lib in bundle.third_party_libraries_mut() {
+        for license in &mut lib.licenses {
+            if license.alternates.is_empty() {
+                continue;
+            }
+
+            let mut candidates = Candidate::all(license);
+            if license.pinned {
+                writeln!(
+                    out,
+                    "\n{}:{} - upstream text of pinned {} license has changed",
+                    lib.package_name, lib.package_version, license.license
+                )?;
+            } else {
+                writeln!(
+                    out,
+                    "\n{}:{} - {} license matched {} files",
+                    lib.package_name,
+                    lib.package_version,
+                    license.license,
+                    candidates.len()
+                )?;
+            }
+            print_candidates(&mut out, &candidates)?;
+
+            let choice = loop {
+                write!(
+                    out,
+                    "Pin which candidate? [1-{}, s to skip, q to quit]: ",
+                    candidates.len()
+                )?;
+                out.flush()?;
+                let mut answer = String::new();
+                if input.read_line(&mut answer)? == 0 {
+                    return Ok(pinned);
+                }
+                match answer.trim() {
+                    "q" => return Ok(pinned),
+                    "s" => break None,
+                    answer => match answer.parse::<usize>() {
+                        Ok(n) if (1..=candidates.len()).contains(&n) => break Some(n - 1),
+                        _ => writeln!(out, "Unknown choice {answer:?}")?,
+                    },
+                }
+            };
+
+            if let Some(choice) = choice {
+                let candidate = candidates.swap_remove(choice);
+                license.pin(candidate.text);
+                license.source_path = candidate.source_path;
+                license.match_score = candidate.match_score;
+                pinned += 1;
+            }
+        }
+    }
+    Ok(pinned)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use bundle_licenses_lib::{finalized_license::text_hash, format::Format};
+
+    use super::*;
+
+    #[test]
+    fn pin_chosen_candidate() {
+        let yaml = format!(
+            r#"
+root_name: root
+third_party_libraries:
+- package_name: ambiguous
+  package_version: 1.0.0
+  license: MIT
+  licenses:
+  - license: MIT
+    text: first
+    source_path: LICENSE
+    alternates:
+    - source_path: LICENSE-MIT
+      text: second
+- package_name: pinned
+  package_version: 1.0.0
+  license: MIT
+  licenses:
+  - license: MIT
+    text: pinned text
+    pinned: true
+    upstream_hash: {}
+    alternates:
+    - text: changed upstream
+- package_name: unambiguous
+  package_version: 1.0.0
+  license: MIT
+  licenses:
+  - license: MIT
+    text: only
+"#,
+            text_hash("old upstream")
+        );
+        let mut bundle = Format::Yaml
+            .deserialize_from_reader(yaml.as_bytes())
+            .unwrap();
+
+        let mut out = vec![];
+        let pinned = review(&mut bundle, Cursor::new("3\nx\n2\n1\n"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(pinned, 2);
+        assert!(out.contains("ambiguous:1.0.0 - MIT license matched 2 files"));
+        assert!(out.contains("Unknown choice \"3\""));
+        assert!(out.contains("pinned:1.0.0 - upstream text of pinned MIT license has changed"));
+        assert!(!out.contains("unambiguous"));
+
+        let libs = bundle.third_party_libraries();
+        let chosen = &libs[0].licenses[0];
+        assert_eq!(chosen.text, "second");
+        assert!(chosen.pinned);
+        assert_eq!(chosen.source_path, Some(PathBuf::from("LICENSE-MIT")));
+        assert_eq!(chosen.upstream_hash, Some(text_hash("first")));
+        assert!(chosen.alternates.is_empty());
+
+        let kept = &libs[1].licenses[0];
+        assert_eq!(kept.text, "pinned text");
+        assert_eq!(kept.upstream_hash, Some(text_hash("changed upstream")));
+        assert!(kept.alternates.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+//! Configuration of a bundle, read from a `bundle-licenses.toml` file in the workspace root or from the
+//! `[workspace.metadata.bundle-licenses]` / `[package.metadata.bundle-licenses]` tables of the root `Cargo.toml`.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::MetadataCommand;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    bundle::Schema,
+    diagnostic::{Diagnostic, DiagnosticKind, DiagnosticsFormat},
+    discovery::Confidence,
+    finalized_license::MetadataField,
+    format::Format,
+    policy::Policy,
+};
+
+/// The name of the config file looked for in the workspace root.
+pub const CONFIG_FILE_NAME: &str = "bundle-licenses.toml";
+/// The key of the config table in the `Cargo.toml` metadata.
+const METADATA_KEY: &str = "bundle-licenses";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Unable to parse {path}: {source}")]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Unable to parse [{table}.metadata.{METADATA_KEY}]: {source}")]
+    Metadata {
+        table: &'static str,
+        source: serde_json::Error,
+    },
+}
+
+/// All options of a bundle.
+///
+/// Relative paths are relative to the directory of the config file, or the workspace root for config
+/// read from `Cargo.toml` metadata.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// The format to write the output in.
+    pub format: Option<Format>,
+    /// The schema to write the output in.
+    pub schema: Option<Schema>,
+    /// The file to write the output to.
+    pub output: Option<PathBuf>,
+    /// A previous thirdparty file to use to check for differences / pull updates.
+    pub previous: Option<PathBuf>,
+    /// After filling in not-found licenses, check if new is a strict subset of previous.
+    pub check_previous: bool,
+    /// Carry previous texts forward from older versions of a package with an unchanged license.
+    pub carry_forward: bool,
+    /// Additional features to pull dependencies from.
+    pub features: Vec<String>,
+    /// Target triples to limit dependencies to, all targets if empty.
+    pub targets: Vec<String>,
+    /// Preferred licenses to use when multiple licenses are found.
+    pub prefer: Vec<String>,
+    /// Names of packages to leave out of the bundle.
+    pub exclude: Vec<String>,
+    /// Scan all package sources for nested license files and SPDX-License-Identifier headers.
+    pub deep_scan: bool,
+    /// Additional license file names to search for.
+    pub license_file_patterns: Vec<String>,
+    /// A local checkout of spdx/license-list-data.
+    pub spdx_data: Option<PathBuf>,
+    /// A file of manual license overrides keyed by `crate@semver-range`.
+    pub overrides: Option<PathBuf>,
+    /// The format to write diagnostics in.
+    pub diagnostics_format: Option<DiagnosticsFormat>,
+    /// The file to write diagnostics to.
+    pub diagnostics_output: Option<PathBuf>,
+    /// Kinds of diagnostics that fail the run.
+    pub fail_on: Vec<DiagnosticKind>,
+    /// Fail the run on license texts found with less than this confidence.
+    pub min_confidence: Option<Confidence>,
+    /// Names of packages to not report any diagnostics for, except for policy violations.
+    pub allow_warnings: Vec<String>,
+    /// Record the source path, confidence and match score of license texts in the bundle.
+    pub match_details: bool,
+    /// Package metadata from the Cargo.toml to include in the bundle.
+    pub metadata_fields: Vec<MetadataField>,
+    /// The number of threads to look for license files with, one per CPU if unset.
+    pub jobs: Option<usize>,
+    /// Don't reuse the license texts found in registry packages by previous runs.
+    pub no_cache: bool,
+    /// The licenses that are allowed or denied.
+    pub policy: Policy,
+}
+
+impl Config {
+    /// Read a config file.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path)?;
+        let config: Self = toml::from_str(&contents).map_err(|source| ConfigError::Toml {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(config.relative_to(path.parent().unwrap_or_else(|| Path::new(""))))
+    }
+
+    /// Find the config of the workspace the current directory is in.
+    ///
+    /// A `bundle-licenses.toml` file in the workspace root takes precedence over `[workspace.metadata.bundle-licenses]`,
+    /// which takes precedence over `[package.metadata.bundle-licenses]` of the root package. Outside of a workspace,
+    /// or if `cargo metadata` fails, there is no config, so that commands working on a bundle file run anywhere.
+    pub fn discover() -> Result<Option<Self>, ConfigError> {
+        let metadata = match MetadataCommand::new().no_deps().exec() {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                log::debug!("No workspace to read the config of: {err}");
+                return Ok(None);
+            }
+        };
+        let root = metadata.workspace_root.as_std_path();
+
+        let config_file = root.join(CONFIG_FILE_NAME);
+        if config_file.is_file() {
+            return Self::from_path(&config_file).map(Some);
+        }
+
+        if let Some(value) = metadata.workspace_metadata.get(METADATA_KEY) {
+            let config: Self =
+                serde_json::from_value(value.clone()).map_err(|source| ConfigError::Metadata {
+                    table: "workspace",
+                    source,
+                })?;
+            return Ok(Some(config.relative_to(root)));
+        }
+
+        if let Some(value) = metadata
+            .root_package()
+            .and_then(|package| package.metadata.get(METADATA_KEY))
+        {
+            let config: Self =
+                serde_json::from_value(value.clone()).map_err(|source| ConfigError::Metadata {
+                    table: "package",
+                    source,
+                })?;
+            return Ok(Some(config.relative_to(root)));
+        }
+
+        Ok(None)
+    }
+
+    /// Should a diagnostic fail the run, policy violations always do.
+    pub fn fails_on(&self, diagnostic: &Diagnostic) -> bool {
+        diagnostic.kind == DiagnosticKind::PolicyViolation
+            || self.fail_on.contains(&diagnostic.kind)
+            || self.min_confidence.is_some_and(|minimum| {
+                diagnostic
+                    .kind
+                    .confidence()
+                    .is_some_and(|confidence| confidence.is_below(minimum))
+            })
+    }
+
+    /// Make all relative paths relative to the given directory.
+    fn relative_to(mut self, dir: &Path) -> Self {
+        let paths = [
+            &mut self.output,
+            &mut self.previous,
+            &mut self.spdx_data,
+            &mut self.overrides,
+            &mut self.diagnostics_output,
+        ];
+        for path in IntoIterator::into_iter(paths).flatten() {
+            if path.is_relative() && path.as_os_str() != "-" {
+                *path = dir.join(&*path);
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn from_path() {
+        let dir = TempDir::new("config");
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(
+            &path,
+            r#"
+                format = "yaml"
+                output = "THIRDPARTY.yml"
+                previous = "/absolute/THIRDPARTY.yml"
+                diagnostics-output = "-"
+                deep-scan = true
+                fail-on = ["missing"]
+                min-confidence = "unsure"
+
+                [policy]
+                deny = ["GPL-3.0"]
+            "#,
+        )
+        .unwrap();
+        let config = Config::from_path(&path).unwrap();
+        assert!(matches!(config.format, Some(Format::Yaml)));
+        assert_eq!(config.output, Some(dir.join("THIRDPARTY.yml")));
+        assert_eq!(
+            config.previous,
+            Some(PathBuf::from("/absolute/THIRDPARTY.yml"))
+        );
+        assert_eq!(config.diagnostics_output, Some(PathBuf::from("-")));
+        assert!(config.deep_scan);
+        assert!(!config.check_previous);
+        assert_eq!(config.fail_on, vec![DiagnosticKind::Missing]);
+        assert_eq!(config.min_confidence, Some(Confidence::Unsure));
+        assert_eq!(config.policy.deny, vec!["GPL-3.0".to_owned()]);
+
+        fs::write(&path, "deep_scan = true\n").unwrap();
+        let err = Config::from_path(&path).unwrap_err();
+        assert!(matches!(err, ConfigError::Toml { .. }));
+        assert!(err.to_string().contains("unknown field `deep_scan`"));
+    }
+
+    #[test]
+    fn fails_on() {
+        let diagnostic = |kind| Diagnostic {
+            package_name: "a".to_owned(),
+            package_version: "1.0.0".to_owned(),
+            license: "MIT".to_owned(),
+            kind,
+            paths: vec![],
+            manifest_path: PathBuf::from("/a/Cargo.toml"),
+            lockfile: None,
+            message: String::new(),
+        };
+
+        let config = Config::default();
+        assert!(config.fails_on(&diagnostic(DiagnosticKind::PolicyViolation)));
+        assert!(!config.fails_on(&diagnostic(DiagnosticKind::Missing)));
+
+        let config = Config {
+            fail_on: vec![DiagnosticKind::LicenseMismatch],
+            min_confidence: Some(Confidence::MultiplePossibleLicenseFiles),
+            ..Config::default()
+        };
+        assert!(config.fails_on(&diagnostic(DiagnosticKind::LicenseMismatch)));
+        assert!(config.fails_on(&diagnostic(DiagnosticKind::Unsure)));
+        assert!(config.fails_on(&diagnostic(DiagnosticKind::Canonical)));
+        assert!(!config.fails_on(&diagnostic(DiagnosticKind::MultipleFiles)));
+        assert!(!config.fails_on(&diagnostic(DiagnosticKind::SemiConfident)));
+        assert!(!config.fails_on(&diagnostic(DiagnosticKind::UndeclaredLicense)));
+
+        let config = Config {
+            min_confidence: Some(Confidence::SemiConfident),
+            ..Config::default()
+        };
+        assert!(config.fails_on(&diagnostic(DiagnosticKind::MultipleFiles)));
+    }
+}
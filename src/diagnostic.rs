@@ -0,0 +1,331 @@
+//! Structured diagnostics about the licenses found for each package, for use by CI annotation tools.
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::Package;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString, VariantNames};
+use thiserror::Error;
+
+use crate::{discovery::Confidence, sarif};
+
+#[derive(Debug, Error)]
+pub enum DiagnosticError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// The kind of issue a [`Diagnostic`] reports.
+#[derive(
+    Display,
+    EnumString,
+    VariantNames,
+    Deserialize,
+    Serialize,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagnosticKind {
+    /// The license text only partially matches the template of the license.
+    SemiConfident,
+    /// The license text barely matches the template of the license.
+    Unsure,
+    /// There is no template to check the license text against.
+    NoTemplate,
+    /// Several equally good license files were found.
+    MultipleFiles,
+    /// No license text was found.
+    Missing,
+    /// No license text was found and the canonical SPDX text is used instead.
+    Canonical,
+    /// The package does not specify a license.
+    Unspecified,
+    /// A deep scan found a license that is not part of the declared license.
+    UndeclaredLicense,
+    /// A deep scan found a license file or header that could not be identified.
+    UnidentifiedLicense,
+    /// The license is not allowed by the license policy.
+    PolicyViolation,
+    /// A license file found for a declared license matches the template of a different license.
+    LicenseMismatch,
+    /// A `REUSE.toml` or `.reuse/dep5` file could not be read and is ignored.
+    InvalidReuse,
+}
+
+impl DiagnosticKind {
+    /// The kind of issue with a license text found with the given confidence, if any.
+    pub fn from_confidence(confidence: Confidence) -> Option<Self> {
+        match confidence {
+            Confidence::Confident => None,
+            Confidence::SemiConfident => Some(Self::SemiConfident),
+            Confidence::Unsure => Some(Self::Unsure),
+            Confidence::NoTemplate => Some(Self::NoTemplate),
+            Confidence::MultiplePossibleLicenseFiles => Some(Self::MultipleFiles),
+            Confidence::MissingLicenseFile => Some(Self::Missing),
+            Confidence::UnspecifiedLicenseInPackage => Some(Self::Unspecified),
+        }
+    }
+
+    /// The confidence in the license text this kind of diagnostic is about, if it is about one.
+    pub fn confidence(self) -> Option<Confidence> {
+        match self {
+            Self::SemiConfident => Some(Confidence::SemiConfident),
+            Self::Unsure => Some(Confidence::Unsure),
+            Self::NoTemplate => Some(Confidence::NoTemplate),
+            Self::MultipleFiles => Some(Confidence::MultiplePossibleLicenseFiles),
+            Self::Missing | Self::Canonical => Some(Confidence::MissingLicenseFile),
+            Self::Unspecified => Some(Confidence::UnspecifiedLicenseInPackage),
+            Self::UndeclaredLicense
+            | Self::UnidentifiedLicense
+            | Self::PolicyViolation
+            | Self::LicenseMismatch
+            | Self::InvalidReuse => None,
+        }
+    }
+
+    /// Is this a diagnostic about the text of a license declared by the package.
+    pub fn is_about_text(self) -> bool {
+        self.confidence().is_some() || self == Self::LicenseMismatch
+    }
+}
+
+/// The line of a package in a `Cargo.lock` file.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockfileLocation {
+    pub path: PathBuf,
+    pub line: usize,
+}
+
+/// An issue with the license of a package.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub package_name: String,
+    pub package_version: String,
+    /// The license the diagnostic is about.
+    pub license: String,
+    pub kind: DiagnosticKind,
+    /// The license files or source files involved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathBuf>,
+    pub manifest_path: PathBuf,
+    /// Where the package is listed in the workspace `Cargo.lock`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lockfile: Option<LockfileLocation>,
+    /// A human readable description of the issue.
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(
+        package: &Package,
+        license: impl fmt::Display,
+        kind: DiagnosticKind,
+        paths: Vec<PathBuf>,
+        message: String,
+    ) -> Self {
+        Self {
+            package_name: package.name.to_string(),
+            package_version: package.version.to_string(),
+            license: license.to_string(),
+            kind,
+            paths,
+            manifest_path: package.manifest_path.clone().into_std_path_buf(),
+            lockfile: None,
+            message,
+        }
+    }
+}
+
+/// Find the line of the package of each diagnostic in a `Cargo.lock` file.
+pub fn locate_in_lockfile(
+    diagnostics: &mut [Diagnostic],
+    lockfile: &Path,
+) -> Result<(), DiagnosticError> {
+    let contents = fs::read_to_string(lockfile)?;
+    let lines = lockfile_lines(&contents);
+    for diagnostic in diagnostics {
+        let key = (
+            diagnostic.package_name.as_str(),
+            diagnostic.package_version.as_str(),
+        );
+        if let Some(&line) = lines.get(&key) {
+            diagnostic.lockfile = Some(LockfileLocation {
+                path: lockfile.to_path_buf(),
+                line,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Map the name and version of each package in a `Cargo.lock` file to the 1-based line of its name.
+fn lockfile_lines(contents: &str) -> HashMap<(&str, &str), usize> {
+    fn value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+        line.strip_prefix(key)
+            .and_then(|rest| rest.trim().strip_prefix('='))
+            .map(|rest| rest.trim().trim_matches('"'))
+    }
+
+    let mut lines = HashMap::new();
+    let mut name = None;
+    for (i, line) in contents.lines().enumerate() {
+        if line.starts_with("[[package]]") {
+            name = None;
+        } else if let Some(n) = value(line, "name") {
+            name = Some((n, i + 1));
+        } else if let (Some(version), Some((n, line))) = (value(line, "version"), name) {
+            lines.insert((n, version), line);
+        }
+    }
+    lines
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The formats diagnostics can be written in.
+#[derive(EnumString, VariantNames, Deserialize, Serialize, Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagnosticsFormat {
+    /// Log each diagnostic as a warning.
+    #[default]
+    Log,
+    /// A JSON array of diagnostics.
+    Json,
+    /// A SARIF 2.1.0 log for code scanning tools.
+    Sarif,
+}
+
+impl DiagnosticsFormat {
+    pub fn write<W: Write>(
+        self,
+        mut writer: W,
+        diagnostics: &[Diagnostic],
+    ) -> Result<(), DiagnosticError> {
+        match self {
+            DiagnosticsFormat::Log => {
+                for diagnostic in diagnostics {
+                    writeln!(writer, "{diagnostic}")?;
+                }
+            }
+            DiagnosticsFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, diagnostics)?;
+                writeln!(writer)?;
+            }
+            DiagnosticsFormat::Sarif => {
+                serde_json::to_writer_pretty(&mut writer, &sarif::to_sarif(diagnostics))?;
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lockfile() {
+        let contents = r#"# This file is automatically @generated by Cargo.
+version = 4
+
+[[package]]
+name = "anyhow"
+version = "1.0.100"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "bitflags"
+version = "1.3.2"
+
+[[package]]
+name = "bitflags"
+version = "2.9.4"
+"#;
+        let lines = lockfile_lines(contents);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[&("anyhow", "1.0.100")], 5);
+        assert_eq!(lines[&("bitflags", "1.3.2")], 10);
+        assert_eq!(lines[&("bitflags", "2.9.4")], 14);
+    }
+
+    #[test]
+    fn json_output() {
+        let diagnostics = vec![
+            Diagnostic {
+                package_name: "a".to_owned(),
+                package_version: "1.0.0".to_owned(),
+                license: "MIT".to_owned(),
+                kind: DiagnosticKind::SemiConfident,
+                paths: vec![PathBuf::from("/a/LICENSE")],
+                manifest_path: PathBuf::from("/a/Cargo.toml"),
+                lockfile: Some(LockfileLocation {
+                    path: PathBuf::from("/Cargo.lock"),
+                    line: 5,
+                }),
+                message: "Confidence level SEMI".to_owned(),
+            },
+            Diagnostic {
+                package_name: "b".to_owned(),
+                package_version: "2.0.0".to_owned(),
+                license: "".to_owned(),
+                kind: DiagnosticKind::Unspecified,
+                paths: vec![],
+                manifest_path: PathBuf::from("/b/Cargo.toml"),
+                lockfile: None,
+                message: "License is not specified".to_owned(),
+            },
+        ];
+
+        let mut output = vec![];
+        DiagnosticsFormat::Json
+            .write(&mut output, &diagnostics)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "package_name": "a",
+                    "package_version": "1.0.0",
+                    "license": "MIT",
+                    "kind": "semi-confident",
+                    "paths": ["/a/LICENSE"],
+                    "manifest_path": "/a/Cargo.toml",
+                    "lockfile": { "path": "/Cargo.lock", "line": 5 },
+                    "message": "Confidence level SEMI"
+                },
+                {
+                    "package_name": "b",
+                    "package_version": "2.0.0",
+                    "license": "",
+                    "kind": "unspecified",
+                    "manifest_path": "/b/Cargo.toml",
+                    "message": "License is not specified"
+                }
+            ])
+        );
+        assert!(output.ends_with(b"]\n"));
+    }
+}
@@ -1,14 +1,36 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, RwLock},
+};
 
 use cargo_metadata::Package;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use slug::slugify;
 use thiserror::Error;
 
-use crate::license::License;
+use crate::{
+    cache::DiscoveryCache,
+    license::License,
+    reuse::{Reuse, ReuseError},
+    spdx_data::SpdxData,
+};
 
 const HIGH_CONFIDENCE_LIMIT: f32 = 0.10;
 const LOW_CONFIDENCE_LIMIT: f32 = 0.15;
+/// Number of bytes read from the head of each source file when looking for SPDX headers.
+const HEADER_SCAN_BYTES: u64 = 8 * 1024;
+/// Directories that are never descended into when deep scanning a package.
+const SKIPPED_SCAN_DIRS: &[&str] = &[".git", "target"];
+
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\w+").unwrap());
+static SPDX_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"SPDX-License-Identifier:\s*([^\r\n]+?)\s*(?:\*/|-->|$)").unwrap()
+});
 
 #[derive(Debug, Error)]
 pub enum DiscoveryError {
@@ -16,7 +38,9 @@ pub enum DiscoveryError {
     Io(#[from] std::io::Error),
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
 pub enum Confidence {
     MultiplePossibleLicenseFiles,
     MissingLicenseFile,
@@ -27,28 +51,99 @@ pub enum Confidence {
     UnspecifiedLicenseInPackage,
 }
 
-#[derive(Debug)]
+impl Confidence {
+    /// Rank of the confidence from best (0) to worst.
+    ///
+    /// Several equally good license files rank just below a partial match, as one of them is likely the right text.
+    fn rank(self) -> u8 {
+        match self {
+            Confidence::Confident => 0,
+            Confidence::SemiConfident => 1,
+            Confidence::MultiplePossibleLicenseFiles => 2,
+            Confidence::Unsure => 3,
+            Confidence::NoTemplate => 4,
+            Confidence::MissingLicenseFile => 5,
+            Confidence::UnspecifiedLicenseInPackage => 6,
+        }
+    }
+
+    /// Is this a worse confidence than the given minimum.
+    pub fn is_below(self, minimum: Self) -> bool {
+        self.rank() > minimum.rank()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct LicenseText {
     pub path: PathBuf,
     pub text: String,
     pub confidence: Confidence,
+    /// How well the text matches the template of the license, from 0 to 1, if it was checked against one.
+    pub match_score: Option<f32>,
+    /// Another license the text matches better, for texts that don't confidently match their own license.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identified: Option<String>,
+}
+
+impl LicenseText {
+    /// Check a license text against the template of the license.
+    fn checked(path: PathBuf, text: String, license: &License, options: &DiscoveryOptions) -> Self {
+        let distance = template_distance(&text, license, options);
+        let confidence = confidence_of(distance);
+        // Texts that confidently match the declared license can't match another one better
+        let identified = (confidence != Confidence::Confident)
+            .then(|| identify_license(&text, options))
+            .flatten()
+            .map(|(identified, _)| identified)
+            .filter(|identified| identified != license)
+            .map(|identified| identified.to_string());
+        Self {
+            path,
+            text,
+            confidence,
+            match_score: distance.map(|distance| (1.0 - distance).clamp(0.0, 1.0)),
+            identified,
+        }
+    }
+}
+
+/// Where a license found by [`scan_package_sources`] came from.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ScanKind {
+    /// A LICENSE-like file in a subdirectory of the package, i.e. vendored code.
+    NestedFile,
+    /// An `SPDX-License-Identifier` header in a source file.
+    SpdxHeader,
+    /// An annotation in a REUSE `.reuse/dep5` or `REUSE.toml` file.
+    ReuseAnnotation,
 }
 
-fn add_frequencies(freq: &mut HashMap<String, u32>, text: &str) {
-    for word in Regex::new(r"\w+").unwrap().find_iter(text) {
+/// A license found while deep scanning the sources of a package.
+#[derive(Debug)]
+pub struct ScannedLicense {
+    pub path: PathBuf,
+    /// The identified license, or `None` if a nested license file matched no known template.
+    pub license: Option<License>,
+    pub kind: ScanKind,
+}
+
+type Frequencies = HashMap<String, u32>;
+
+fn add_frequencies(freq: &mut Frequencies, text: &str) {
+    for word in WORD.find_iter(text) {
         *freq
             .entry(word.as_str().to_lowercase().clone())
             .or_insert(0) += 1;
     }
 }
 
-fn calculate_frequency(text: &str) -> HashMap<String, u32> {
+fn calculate_frequency(text: &str) -> Frequencies {
     let mut freq = HashMap::new();
     add_frequencies(&mut freq, text);
     freq
 }
 
-fn compare(mut text_freq: HashMap<String, u32>, template_freq: &HashMap<String, u32>) -> u32 {
+fn compare(mut text_freq: Frequencies, template_freq: &Frequencies) -> u32 {
     let mut errors = 0;
 
     for (word, &count) in template_freq {
@@ -64,85 +159,218 @@ fn compare(mut text_freq: HashMap<String, u32>, template_freq: &HashMap<String,
     errors
 }
 
-fn check_against_template(text: &str, license: &License) -> Confidence {
+/// The fraction of words that differ between a text and the template of the license, or None if there is no template.
+fn template_distance(text: &str, license: &License, options: &DiscoveryOptions) -> Option<f32> {
     let text_freq = calculate_frequency(text);
 
     let template_freq = if let License::Multiple(ref licenses) = *license {
-        let mut template_freq = HashMap::new();
+        let mut template_freq = Frequencies::new();
         for license in licenses {
-            if let Some(template) = license.template() {
-                add_frequencies(&mut template_freq, template);
-            } else {
-                return Confidence::NoTemplate;
+            for (word, count) in options.template_frequencies(license)?.iter() {
+                *template_freq.entry(word.clone()).or_insert(0) += count;
             }
         }
-        template_freq
-    } else if let Some(template) = license.template() {
-        calculate_frequency(template)
+        Arc::new(template_freq)
     } else {
-        return Confidence::NoTemplate;
+        options.template_frequencies(license)?
     };
 
     let total: u32 = template_freq.values().sum();
     let errors = compare(text_freq, &template_freq);
-    let score = (errors as f32) / (total as f32);
+    Some((errors as f32) / (total as f32))
+}
 
-    if score < HIGH_CONFIDENCE_LIMIT {
-        Confidence::Confident
-    } else if score < LOW_CONFIDENCE_LIMIT {
-        Confidence::SemiConfident
-    } else {
-        Confidence::Unsure
+fn confidence_of(distance: Option<f32>) -> Confidence {
+    match distance {
+        None => Confidence::NoTemplate,
+        Some(score) if score < HIGH_CONFIDENCE_LIMIT => Confidence::Confident,
+        Some(score) if score < LOW_CONFIDENCE_LIMIT => Confidence::SemiConfident,
+        Some(_) => Confidence::Unsure,
     }
 }
 
-pub fn find_package_license(
-    package: &Package,
-    license: &License,
-) -> Result<Vec<LicenseText>, DiscoveryError> {
-    /// Is this a generic license name
-    fn generic_license_name(name: &str) -> bool {
-        name.to_uppercase() == "LICENSE"
-            || name.to_uppercase() == "LICENCE"
-            || name.to_uppercase() == "LICENSE.MD"
-            || name.to_uppercase() == "LICENSE.TXT"
-            || name.to_uppercase() == "COPYING"
-    }
-
-    fn name_matches(name: &str, license: &License) -> bool {
-        let name = slugify(name).to_lowercase();
-        match *license {
-            License::Custom(ref custom) => {
-                let custom = slugify(custom).to_lowercase();
-                name == custom
-                    || name == format!("license-{}", custom)
-                    || name == format!("license-{}-md", custom)
-                    || name == format!("license-{}-txt", custom)
-                    || name == format!("{}-license", custom)
-                    || name == format!("{}-license-md", custom)
-                    || name == format!("{}-license-txt", custom)
+/// The file name patterns used to find license files in a package.
+///
+/// Names are compared case-insensitively after stripping any of the known `extensions`. Specific
+/// patterns contain an `{id}` placeholder that is replaced by each synonym of the license searched for.
+#[derive(Debug, Clone)]
+pub struct LicenseFilePatterns {
+    /// Names of files that may hold any license, i.e. `LICENSE`.
+    pub generic: Vec<String>,
+    /// Names of files that hold one specific license, i.e. `LICENSE-{id}`.
+    pub specific: Vec<String>,
+    /// Extensions that are ignored when matching names, i.e. `md` in `LICENSE.md`.
+    pub extensions: Vec<String>,
+    /// Directories that hold license files, i.e. the REUSE `LICENSES` directory.
+    pub directories: Vec<String>,
+}
+
+impl Default for LicenseFilePatterns {
+    fn default() -> Self {
+        let to_strings = |names: &[&str]| names.iter().map(|&n| String::from(n)).collect();
+        Self {
+            generic: to_strings(&["LICENSE", "LICENCE", "COPYING", "COPYRIGHT"]),
+            specific: to_strings(&[
+                "{id}",
+                "LICENSE-{id}",
+                "LICENCE-{id}",
+                "{id}-LICENSE",
+                "COPYING-{id}",
+            ]),
+            extensions: to_strings(&["md", "markdown", "txt", "rst"]),
+            directories: to_strings(&["LICENSES", "LICENSE", "LICENCES", "LICENCE"]),
+        }
+    }
+}
+
+impl LicenseFilePatterns {
+    /// Add a pattern, which is specific if it contains an `{id}` placeholder and generic otherwise.
+    pub fn add(&mut self, pattern: &str) {
+        if pattern.contains("{id}") {
+            self.specific.push(pattern.to_owned());
+        } else {
+            self.generic.push(pattern.to_owned());
+        }
+    }
+
+    /// A file name with any known extension removed.
+    fn stem<'a>(&self, name: &'a str) -> &'a str {
+        match name.rsplit_once('.') {
+            Some((stem, ext))
+                if self
+                    .extensions
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(ext)) =>
+            {
+                stem
             }
-            ref license => {
-                let mut found = false;
-                for lic in license.synonyms() {
-                    if name == lic
-                        || name == format!("license-{}", lic)
-                        || name == format!("license-{}-md", lic)
-                        || name == format!("license-{}-txt", lic)
-                        || name == format!("{}-license", lic)
-                        || name == format!("{}-license-md", lic)
-                        || name == format!("{}-license-txt", lic)
-                    {
-                        found = true;
-                        break;
-                    }
-                }
-                found
+            _ => name,
+        }
+    }
+
+    /// Slugify a file name with any known extension removed.
+    fn normalize(&self, name: &str) -> String {
+        slugify(self.stem(name)).to_lowercase()
+    }
+
+    /// The position of the generic license name this name matches, earlier names are preferred
+    fn generic_rank(&self, name: &str) -> Option<usize> {
+        let name = self.normalize(name);
+        self.generic
+            .iter()
+            .position(|generic| slugify(generic).to_lowercase() == name)
+    }
+
+    /// Is this the name of a file for the given license
+    fn is_specific(&self, name: &str, license: &License) -> bool {
+        let name = self.normalize(name);
+        let ids = match *license {
+            License::Custom(ref custom) => vec![slugify(custom).to_lowercase()],
+            ref license => license.synonyms(),
+        };
+        ids.iter().any(|id| {
+            self.specific
+                .iter()
+                .any(|pattern| slugify(pattern.replace("{id}", id)).to_lowercase() == name)
+        })
+    }
+
+    /// Is this the name of a license file of any license, as looked for when scanning package sources.
+    ///
+    /// A specific pattern without any fixed part, i.e. `{id}`, only matches SPDX license identifiers.
+    fn is_license_file(&self, name: &str) -> bool {
+        if self.generic_rank(name).is_some() {
+            return true;
+        }
+        let normalized = self.normalize(name);
+        self.specific.iter().any(|pattern| {
+            let (prefix, suffix) = pattern.split_once("{id}").unwrap_or((pattern, ""));
+            let prefix = slugify(prefix).to_lowercase();
+            let suffix = slugify(suffix).to_lowercase();
+            if prefix.is_empty() && suffix.is_empty() {
+                return spdx::license_id(self.stem(name)).is_some();
             }
+            let rest = match prefix.as_str() {
+                "" => Some(normalized.as_str()),
+                prefix => normalized
+                    .strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_prefix('-')),
+            };
+            let id = match suffix.as_str() {
+                "" => rest,
+                suffix => rest
+                    .and_then(|rest| rest.strip_suffix(suffix))
+                    .and_then(|rest| rest.strip_suffix('-')),
+            };
+            id.is_some_and(|id| !id.is_empty())
+        })
+    }
+
+    /// Is this a directory that holds license files
+    fn is_directory(&self, name: &str) -> bool {
+        self.directories
+            .iter()
+            .any(|directory| directory.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The word frequencies of license templates, computed once and shared between clones and threads.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateCache(Arc<RwLock<HashMap<License, Option<Arc<Frequencies>>>>>);
+
+/// Options that control how license files are discovered.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryOptions {
+    pub patterns: LicenseFilePatterns,
+    /// License list data used for licenses without a bundled template.
+    pub spdx_data: Option<SpdxData>,
+    pub template_cache: TemplateCache,
+    /// Texts found in registry packages by previous runs.
+    pub cache: Option<Arc<DiscoveryCache>>,
+}
+
+impl DiscoveryOptions {
+    /// The template to compare found license files against, preferring the bundled templates.
+    pub fn template(&self, license: &License) -> Option<Cow<'static, str>> {
+        license.template().map(Cow::Borrowed).or_else(|| {
+            self.spdx_data
+                .as_ref()
+                .and_then(|data| data.license_text(license))
+                .map(Cow::Owned)
+        })
+    }
+
+    /// The word frequencies of the template of a license, see [`DiscoveryOptions::template`].
+    fn template_frequencies(&self, license: &License) -> Option<Arc<Frequencies>> {
+        let cache = &self.template_cache.0;
+        if let Some(frequencies) = cache.read().unwrap().get(license) {
+            return frequencies.clone();
         }
+        let frequencies = self
+            .template(license)
+            .map(|template| Arc::new(calculate_frequency(&template)));
+        cache
+            .write()
+            .unwrap()
+            .insert(license.clone(), frequencies.clone());
+        frequencies
+    }
+
+    /// The canonical text of a license to use when none could be found in a package.
+    pub fn canonical_text(&self, license: &License) -> Option<String> {
+        self.spdx_data
+            .as_ref()
+            .and_then(|data| data.license_text(license))
     }
+}
 
-    let mut generic = None;
+pub fn find_package_license(
+    package: &Package,
+    license: &License,
+    options: &DiscoveryOptions,
+) -> Result<Vec<LicenseText>, DiscoveryError> {
+    let patterns = &options.patterns;
+    let mut generic: Option<(usize, LicenseText)> = None;
     let mut texts = vec![];
 
     // if LICENSE file is not found in current_dir
@@ -153,28 +381,56 @@ pub fn find_package_license(
             continue;
         }
 
+        // REUSE projects hold the exact text of each license in LICENSES/<SPDX-ID>.txt, malformed REUSE data is
+        // reported by `reuse_errors` and ignored here
+        if let Ok(Some(reuse)) = Reuse::load(current_dir.as_std_path()) {
+            if let Some((path, text)) = reuse.license_text(license) {
+                texts.push(LicenseText {
+                    path,
+                    text,
+                    confidence: Confidence::Confident,
+                    match_score: None,
+                    identified: None,
+                });
+                break;
+            }
+        }
+
+        // License files may also live in a dedicated directory, i.e. LICENSES/MIT.txt
+        let mut dirs = vec![current_dir.as_std_path().to_path_buf()];
         for entry in fs::read_dir(current_dir)? {
             let entry = entry?;
-            let path = entry.path().clone();
-            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir()
+                && patterns.is_directory(&entry.file_name().to_string_lossy())
+            {
+                dirs.push(entry.path());
+            }
+        }
 
-            if name_matches(&name, license) {
-                if let Ok(text) = fs::read_to_string(&path) {
-                    let confidence = check_against_template(&text, license);
-                    texts.push(LicenseText {
-                        path,
-                        text,
-                        confidence,
-                    });
+        for dir in dirs {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    continue;
                 }
-            } else if generic_license_name(&name) {
-                if let Ok(text) = fs::read_to_string(&path) {
-                    let confidence = check_against_template(&text, license);
-                    generic = Some(LicenseText {
-                        path,
-                        text,
-                        confidence,
-                    });
+                let path = entry.path().clone();
+                let name = entry.file_name().to_string_lossy().into_owned();
+
+                if patterns.is_specific(&name, license) {
+                    if let Ok(text) = fs::read_to_string(&path) {
+                        texts.push(LicenseText::checked(path, text, license, options));
+                    }
+                } else if let Some(rank) = patterns.generic_rank(&name) {
+                    // Ties are broken by path to not depend on the order of the directory listing
+                    if generic
+                        .as_ref()
+                        .is_some_and(|(best, best_text)| (*best, &best_text.path) <= (rank, &path))
+                    {
+                        continue;
+                    }
+                    if let Ok(text) = fs::read_to_string(&path) {
+                        generic = Some((rank, LicenseText::checked(path, text, license, options)));
+                    }
                 }
             }
         }
@@ -186,20 +442,263 @@ pub fn find_package_license(
     }
 
     if texts.is_empty() {
-        if let Some(generic) = generic {
+        if let Some((_, generic)) = generic {
             texts.push(generic);
         } else if let Some(rel_path) = package.license_file() {
             // Lastly try the specified license file which may work better for workspaces
             if let Ok(text) = fs::read_to_string(&rel_path) {
-                let confidence = check_against_template(&text, license);
-                texts.push(LicenseText {
-                    path: rel_path.into_std_path_buf(),
+                texts.push(LicenseText::checked(
+                    rel_path.into_std_path_buf(),
                     text,
-                    confidence,
-                });
+                    license,
+                    options,
+                ));
             }
         }
     }
 
     Ok(texts)
 }
+
+/// Identify the license a text most likely is by comparing it against every known template.
+///
+/// Only [`Confidence::Confident`] and [`Confidence::SemiConfident`] matches are returned, the closest match wins.
+pub fn identify_license(text: &str, options: &DiscoveryOptions) -> Option<(License, Confidence)> {
+    License::templated()
+        .into_iter()
+        .filter_map(|license| {
+            let distance = template_distance(text, &license, options)?;
+            (distance < LOW_CONFIDENCE_LIMIT).then_some((license, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(license, distance)| (license, confidence_of(Some(distance))))
+}
+
+/// The REUSE data of a package and its workspace that could not be loaded, along with the directory it is in.
+///
+/// Discovery ignores such REUSE data and searches the package as if it did not follow the REUSE layout.
+pub fn reuse_errors(package: &Package) -> Vec<(PathBuf, ReuseError)> {
+    let package_dir = package.manifest_path.parent().unwrap();
+    package_dir
+        .ancestors()
+        .filter(|dir| dir.join("Cargo.toml").exists())
+        .filter_map(|dir| {
+            Reuse::load(dir.as_std_path())
+                .err()
+                .map(|err| (dir.as_std_path().to_path_buf(), err))
+        })
+        .collect()
+}
+
+/// Walk the full source tree of a package looking for nested license files and
+/// `SPDX-License-Identifier` headers, as found in crates that vendor third-party code.
+pub fn scan_package_sources(package: &Package, options: &DiscoveryOptions) -> Vec<ScannedLicense> {
+    fn spdx_header(path: &Path) -> Option<String> {
+        let mut head = String::new();
+        File::open(path)
+            .ok()?
+            .take(HEADER_SCAN_BYTES)
+            .read_to_string(&mut head)
+            .ok()?;
+        head.lines()
+            .find_map(|line| SPDX_HEADER.captures(line))
+            .map(|caps| caps[1].to_owned())
+    }
+
+    /// Unreadable directories and entries are logged and skipped rather than failing the scan.
+    fn walk(dir: &Path, nested: bool, options: &DiscoveryOptions, found: &mut Vec<ScannedLicense>) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::warn!("Unable to read {}: {err}", dir.display());
+                return;
+            }
+        };
+        for entry in entries {
+            let (entry, file_type) = match entry.and_then(|entry| {
+                let file_type = entry.file_type()?;
+                Ok((entry, file_type))
+            }) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    log::warn!("Unable to read an entry of {}: {err}", dir.display());
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if file_type.is_dir() {
+                if !SKIPPED_SCAN_DIRS.contains(&name.as_str()) {
+                    walk(&path, true, options, found);
+                }
+            } else if file_type.is_file() {
+                if nested && options.patterns.is_license_file(&name) {
+                    if let Ok(text) = fs::read_to_string(&path) {
+                        found.push(ScannedLicense {
+                            path,
+                            license: identify_license(&text, options).map(|(license, _)| license),
+                            kind: ScanKind::NestedFile,
+                        });
+                    }
+                } else if let Some(expr) = spdx_header(&path) {
+                    found.push(ScannedLicense {
+                        path,
+                        license: Some(expr.parse::<License>().unwrap()),
+                        kind: ScanKind::SpdxHeader,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut found = vec![];
+    let package_dir = package.manifest_path.parent().unwrap();
+    walk(package_dir.as_std_path(), false, options, &mut found);
+
+    if let Ok(Some(reuse)) = Reuse::load(package_dir.as_std_path()) {
+        for (path, license) in reuse.annotations() {
+            found.push(ScannedLicense {
+                path: path.clone(),
+                license: Some(license.clone()),
+                kind: ScanKind::ReuseAnnotation,
+            });
+        }
+    }
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{package, TempDir};
+
+    #[test]
+    fn license_file_patterns() {
+        let patterns = LicenseFilePatterns::default();
+        assert!(patterns.is_specific("LICENSE-MIT", &License::MIT));
+        assert!(patterns.is_specific("LICENSE-MIT.markdown", &License::MIT));
+        assert!(patterns.is_specific("MIT.txt", &License::MIT));
+        assert!(patterns.is_specific("UNLICENSE", &License::Unlicense));
+        assert!(patterns.is_specific("LICENSE-APACHE", &License::Apache_2_0));
+        assert!(!patterns.is_specific("LICENSE-MIT", &License::Apache_2_0));
+        assert_eq!(patterns.generic_rank("LICENSE"), Some(0));
+        assert_eq!(patterns.generic_rank("license.md"), Some(0));
+        assert_eq!(patterns.generic_rank("COPYRIGHT"), Some(3));
+        assert_eq!(patterns.generic_rank("UNLICENSE"), None);
+        assert!(patterns.is_directory("LICENSES"));
+    }
+
+    #[test]
+    fn custom_license_file_patterns() {
+        let mut patterns = LicenseFilePatterns::default();
+        assert!(!patterns.is_specific("NOTICE-MIT", &License::MIT));
+        patterns.add("NOTICE-{id}");
+        patterns.add("LEGAL");
+        assert!(patterns.is_specific("NOTICE-MIT", &License::MIT));
+        assert!(patterns.generic_rank("LEGAL").is_some());
+
+        // Deep scans look for files of any license with the same patterns
+        assert!(patterns.is_license_file("LEGAL.md"));
+        assert!(patterns.is_license_file("COPYRIGHT"));
+        assert!(patterns.is_license_file("NOTICE-anything"));
+        assert!(patterns.is_license_file("LICENSE_THIRD_PARTY.txt"));
+        assert!(patterns.is_license_file("Apache-2.0.txt"));
+        assert!(!patterns.is_license_file("NOTICE"));
+        assert!(!patterns.is_license_file("LICENSED.rs"));
+        assert!(!patterns.is_license_file("main.rs"));
+    }
+
+    #[test]
+    fn deep_scan() {
+        let tmp = TempDir::new("scan");
+        let dir = tmp.join("scanned");
+        for sub in ["src", "vendor/zlib", "target"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join("LICENSE-MIT"), License::MIT.template().unwrap()).unwrap();
+        fs::write(
+            dir.join("src/lib.rs"),
+            "// SPDX-License-Identifier: MIT OR Apache-2.0\n",
+        )
+        .unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            dir.join("vendor/zlib/LICENSE"),
+            License::Zlib.template().unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("target/LICENSE"), License::ISC.template().unwrap()).unwrap();
+        fs::create_dir_all(dir.join("vendor/bsd")).unwrap();
+        fs::write(
+            dir.join("vendor/bsd/COPYRIGHT"),
+            License::BSD_3_Clause.template().unwrap(),
+        )
+        .unwrap();
+        let package = package("scanned", "MIT", &tmp);
+
+        let found = scan_package_sources(&package, &DiscoveryOptions::default());
+        let found: Vec<_> = found
+            .iter()
+            .map(|scanned| {
+                (
+                    scanned.path.strip_prefix(&dir).unwrap().to_path_buf(),
+                    scanned.license.clone(),
+                    scanned.kind,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    PathBuf::from("src/lib.rs"),
+                    Some("MIT OR Apache-2.0".parse().unwrap()),
+                    ScanKind::SpdxHeader
+                ),
+                (
+                    PathBuf::from("vendor/bsd/COPYRIGHT"),
+                    Some(License::BSD_3_Clause),
+                    ScanKind::NestedFile
+                ),
+                (
+                    PathBuf::from("vendor/zlib/LICENSE"),
+                    Some(License::Zlib),
+                    ScanKind::NestedFile
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn confidence_is_below() {
+        assert!(Confidence::SemiConfident.is_below(Confidence::Confident));
+        assert!(!Confidence::Confident.is_below(Confidence::SemiConfident));
+        assert!(!Confidence::Unsure.is_below(Confidence::Unsure));
+        assert!(Confidence::MultiplePossibleLicenseFiles.is_below(Confidence::SemiConfident));
+        assert!(Confidence::Unsure.is_below(Confidence::MultiplePossibleLicenseFiles));
+        assert!(Confidence::UnspecifiedLicenseInPackage.is_below(Confidence::MissingLicenseFile));
+    }
+
+    #[test]
+    fn identify_closest_license() {
+        let options = DiscoveryOptions::default();
+        // Close to both Apache-2.0 and Apache-2.0 WITH LLVM-exception, which is listed later but matches better
+        let words: Vec<_> = License::Apache_2_0_WITH_LLVM_exception
+            .template()
+            .unwrap()
+            .split_whitespace()
+            .collect();
+        let text = words[200..].join(" ");
+        assert_eq!(
+            identify_license(&text, &options),
+            Some((
+                License::Apache_2_0_WITH_LLVM_exception,
+                Confidence::SemiConfident
+            ))
+        );
+        assert_eq!(identify_license("not a license", &options), None);
+    }
+}
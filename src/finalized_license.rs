@@ -1,20 +1,64 @@
 //! A finalized license representation meant for serializing / deserialzing
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
 use cargo_metadata::Package;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use strum::{Display, EnumString, VariantNames};
 
-use crate::license::License;
+use crate::{discovery::Confidence, license::License};
 
 pub static LICENSE_NOT_FOUNT_TEXT: &str = "NOT FOUND";
 
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// Hash a license text to detect when it changes.
+pub fn text_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct LicenseAndText {
     /// The license itself in SPDX format
     pub license: String,
-    /// The lines of the license text, or NOT FOUND
+    /// The lines of the license text, or NOT FOUND. Empty if the text is in the texts table of the bundle
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    /// The id of the text in the texts table of a deduplicated bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_id: Option<String>,
+    /// The text is the canonical text of the license from the SPDX license list, not from the package
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub canonical: bool,
+    /// The text was set by hand and always wins over newly discovered texts when used as a previous bundle
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// The hash of the discovered text when the license was pinned, used to warn when the package's text changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_hash: Option<String>,
+    /// The file the text was found in, relative to `$CARGO_HOME` if it is in there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
+    /// How confident the match of the text with the license is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// How well the text matches the template of the license, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_score: Option<f32>,
+    /// Other files that matched the license as well as the chosen text, best first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<AlternateText>,
+}
+
+/// A license file that was not chosen for a license, see [`LicenseAndText::alternates`].
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AlternateText {
+    /// The file the text was found in, relative to `$CARGO_HOME` if it is in there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
+    /// How well the text matches the template of the license, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_score: Option<f32>,
+    /// The lines of the license text
     pub text: String,
 }
 
@@ -23,7 +67,109 @@ impl LicenseAndText {
         Self {
             license: license.to_string(),
             text,
+            text_id: None,
+            canonical: false,
+            pinned: false,
+            upstream_hash: None,
+            source_path: None,
+            confidence: None,
+            match_score: None,
+            alternates: vec![],
+        }
+    }
+
+    /// Create a license with the canonical text of the license rather than a text found in the package.
+    pub fn canonical(license: &License, text: String) -> Self {
+        Self {
+            canonical: true,
+            ..Self::new(license, text)
+        }
+    }
+
+    /// Drop the details of how the text and its alternates were found, keeping the alternate texts.
+    pub fn omit_match_details(&mut self) {
+        self.source_path = None;
+        self.confidence = None;
+        self.match_score = None;
+        for alternate in &mut self.alternates {
+            alternate.source_path = None;
+            alternate.match_score = None;
+        }
+    }
+
+    /// Drop the details of how the text was found, along with any alternate texts.
+    pub fn clear_match_details(&mut self) {
+        self.source_path = None;
+        self.confidence = None;
+        self.match_score = None;
+        self.alternates.clear();
+    }
+
+    /// Replace the text by hand and pin it, recording the hash of the current upstream text.
+    ///
+    /// The upstream text is the replaced text, or for a license that is already pinned, the changed upstream text
+    /// kept as an alternate by [`LicenseAndText::apply_previous`]. Pinning again thereby acknowledges the change.
+    pub fn pin(&mut self, text: String) {
+        let upstream = if self.pinned {
+            self.alternates.first().map(|alternate| &alternate.text)
+        } else {
+            Some(&self.text).filter(|_| !self.needs_text())
+        };
+        if let Some(upstream) = upstream {
+            self.upstream_hash = Some(text_hash(upstream));
+        }
+        self.text = text;
+        self.pinned = true;
+        self.canonical = false;
+        self.clear_match_details();
+    }
+
+    /// Does this license still need a text from the package, either from a previous bundle or by hand
+    pub fn needs_text(&self) -> bool {
+        self.text == LICENSE_NOT_FOUNT_TEXT || self.canonical
+    }
+
+    /// Replace the text with the text of a previous license, keeping the pin of the previous license.
+    ///
+    /// Warns if the previous license is pinned and the text discovered for this license has changed since it was pinned,
+    /// in which case the discovered text is kept as the only alternate until the license is pinned again.
+    pub fn apply_previous(&mut self, previous: &LicenseAndText, package_name: &str, version: &str) {
+        if previous.pinned {
+            let upstream_hash = Some(text_hash(&self.text)).filter(|_| !self.needs_text());
+            self.alternates.clear();
+            if let (Some(pinned_hash), Some(upstream_hash)) =
+                (&previous.upstream_hash, &upstream_hash)
+            {
+                if pinned_hash != upstream_hash {
+                    log::warn!(
+                        "Upstream text of pinned {} license has changed in {}:{}, run `cargo bundle-licenses review` to verify the pinned text is still correct",
+                        self.license,
+                        package_name,
+                        version
+                    );
+                    self.alternates.push(AlternateText {
+                        source_path: self.source_path.clone(),
+                        match_score: self.match_score,
+                        text: self.text.clone(),
+                    });
+                }
+            }
+            self.upstream_hash = previous.upstream_hash.clone().or(upstream_hash);
+            self.pinned = true;
         }
+        self.text = previous.text.clone();
+        self.canonical = false;
+        self.source_path = previous.source_path.clone();
+        self.confidence = previous.confidence;
+        self.match_score = previous.match_score;
+    }
+}
+
+/// Licenses are equal if their license and text are equal, how the text came to be or where it was found is not
+/// compared.
+impl PartialEq for LicenseAndText {
+    fn eq(&self, other: &Self) -> bool {
+        self.license == other.license && self.text == other.text
     }
 }
 
@@ -36,10 +182,25 @@ pub struct FinalizedLicense {
     /// The url of the repository from the Cargo.toml.
     #[serde(default)]
     pub repository: String,
+    /// Where the package was taken from when building.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PackageSource>,
+    /// The authors from the Cargo.toml, if requested with [`MetadataField::Authors`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// The homepage from the Cargo.toml, if requested with [`MetadataField::Homepage`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    /// The description from the Cargo.toml, if requested with [`MetadataField::Description`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     /// The full license from the Cargo.toml.
     pub license: String,
     /// The licenses and their associated text.
     pub licenses: Vec<LicenseAndText>,
+    /// The `crate@semver-range` key of the manual override applied to this license, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_override: Option<String>,
 }
 
 impl FinalizedLicense {
@@ -48,15 +209,46 @@ impl FinalizedLicense {
             package_name: package.name.to_string(),
             package_version: package.version.to_string(),
             repository: package.repository.to_owned().unwrap_or_default(),
+            source: Some(PackageSource::of(package)),
+            authors: package.authors.clone(),
+            homepage: package.homepage.clone(),
+            description: package.description.clone(),
             license: package
                 .license
                 .to_owned()
                 .unwrap_or_else(|| license.to_string()),
             licenses,
+            license_override: None,
+        }
+    }
+
+    /// Clear the package metadata that was not asked for.
+    pub fn retain_metadata(&mut self, fields: &[MetadataField]) {
+        if !fields.contains(&MetadataField::Authors) {
+            self.authors.clear();
+        }
+        if !fields.contains(&MetadataField::Homepage) {
+            self.homepage = None;
+        }
+        if !fields.contains(&MetadataField::Description) {
+            self.description = None;
         }
     }
 }
 
+/// Optional package metadata from the Cargo.toml that can be included in the bundle, i.e. for attribution pages.
+#[derive(
+    Display, EnumString, VariantNames, Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq,
+)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum MetadataField {
+    Authors,
+    Homepage,
+    Description,
+}
+
 impl PartialEq for FinalizedLicense {
     fn eq(&self, other: &Self) -> bool {
         if self.package_version != other.package_version || self.package_name != other.package_name
@@ -78,6 +270,79 @@ impl PartialEq for FinalizedLicense {
     }
 }
 
+/// The kind of place a package was taken from.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceKind {
+    Registry,
+    Git,
+    Path,
+}
+
+/// Where a package was taken from, to reproduce exactly what was built.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PackageSource {
+    pub kind: SourceKind,
+    /// The name of the registry, if it is crates.io.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// The index url of a registry or the url of a git repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The resolved commit of a git repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+}
+
+impl PackageSource {
+    pub fn of(package: &Package) -> Self {
+        Self::parse(package.source.as_ref().map(|source| source.repr.as_str()))
+    }
+
+    /// Parse the source of a package as given by `cargo metadata`, which is missing for path dependencies.
+    fn parse(repr: Option<&str>) -> Self {
+        let Some(repr) = repr else {
+            return Self {
+                kind: SourceKind::Path,
+                registry: None,
+                url: None,
+                revision: None,
+            };
+        };
+
+        if let Some(git) = repr.strip_prefix("git+") {
+            // i.e. git+https://github.com/owner/repo?branch=main#<commit>
+            let (location, revision) = match git.split_once('#') {
+                Some((location, revision)) => (location, Some(revision.to_owned())),
+                None => (git, None),
+            };
+            let url = location.split_once('?').map_or(location, |(url, _)| url);
+            return Self {
+                kind: SourceKind::Git,
+                registry: None,
+                url: Some(url.to_owned()),
+                revision,
+            };
+        }
+
+        let url = repr.strip_prefix("registry+").unwrap_or(repr);
+        Self {
+            kind: SourceKind::Registry,
+            registry: CRATES_IO_INDEXES
+                .contains(&url)
+                .then(|| String::from("crates-io")),
+            url: Some(url.to_owned()),
+            revision: None,
+        }
+    }
+}
+
+/// The git and sparse index urls of crates.io.
+const CRATES_IO_INDEXES: &[&str] = &[
+    "https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
 /// Hashable struct to for a package for easy lookup
 // TODO: could probably use package id? or str refs?
 #[derive(Clone, Hash, PartialEq, Eq)]
@@ -113,3 +378,63 @@ pub fn finalized_licenses_lookup(
     }
     map
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn package_source() {
+        let path = PackageSource::parse(None);
+        assert_eq!(path.kind, SourceKind::Path);
+        assert_eq!(path.url, None);
+
+        let crates_io = PackageSource::parse(Some(
+            "registry+https://github.com/rust-lang/crates.io-index",
+        ));
+        assert_eq!(crates_io.kind, SourceKind::Registry);
+        assert_eq!(crates_io.registry.as_deref(), Some("crates-io"));
+
+        let sparse = PackageSource::parse(Some("sparse+https://my-registry.example/index/"));
+        assert_eq!(sparse.registry, None);
+        assert_eq!(
+            sparse.url.as_deref(),
+            Some("sparse+https://my-registry.example/index/")
+        );
+
+        let git = PackageSource::parse(Some(
+            "git+https://github.com/owner/repo?branch=main#0123456789abcdef",
+        ));
+        assert_eq!(git.kind, SourceKind::Git);
+        assert_eq!(git.url.as_deref(), Some("https://github.com/owner/repo"));
+        assert_eq!(git.revision.as_deref(), Some("0123456789abcdef"));
+    }
+
+    #[test]
+    fn pinned_upstream_changes() {
+        let mut pinned = LicenseAndText::new(&License::MIT, "upstream".to_owned());
+        pinned.pin("pinned".to_owned());
+        assert_eq!(pinned.upstream_hash, Some(text_hash("upstream")));
+
+        // The pinned text wins over an unchanged upstream text
+        let mut unchanged = LicenseAndText::new(&License::MIT, "upstream".to_owned());
+        unchanged.apply_previous(&pinned, "a", "1.0.0");
+        assert_eq!(unchanged.text, "pinned");
+        assert!(unchanged.pinned);
+        assert!(unchanged.alternates.is_empty());
+
+        // A changed upstream text is kept as an alternate until the license is pinned again
+        let mut changed = LicenseAndText::new(&License::MIT, "changed".to_owned());
+        changed.apply_previous(&pinned, "a", "1.0.0");
+        assert_eq!(changed.text, "pinned");
+        assert_eq!(changed.upstream_hash, Some(text_hash("upstream")));
+        assert_eq!(changed.alternates[0].text, "changed");
+
+        changed.pin("pinned".to_owned());
+        assert_eq!(changed.upstream_hash, Some(text_hash("changed")));
+        assert!(changed.alternates.is_empty());
+        let mut acknowledged = LicenseAndText::new(&License::MIT, "changed".to_owned());
+        acknowledged.apply_previous(&changed, "a", "1.0.0");
+        assert!(acknowledged.alternates.is_empty());
+    }
+}
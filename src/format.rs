@@ -1,6 +1,11 @@
 //! The allowed serialization / deserialization formats.
 use crate::bundle::Bundle;
-use std::io::{self, Read, Write};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    str::FromStr,
+};
 use strum::{EnumString, VariantNames};
 use thiserror::Error;
 
@@ -18,19 +23,30 @@ pub enum FormatError {
     Yaml(#[from] serde_yaml::Error),
 }
 
-#[derive(EnumString, VariantNames, Debug, Copy, Clone)]
+#[derive(EnumString, VariantNames, Deserialize, Serialize, Debug, Copy, Clone, Default)]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 #[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 pub enum Format {
     #[strum(serialize = "json")]
     Json,
+    #[default]
     #[strum(serialize = "toml", serialize = "tml")]
+    #[serde(alias = "tml")]
     Toml,
     #[strum(serialize = "yaml", serialize = "yml")]
+    #[serde(alias = "yml")]
     Yaml,
 }
 
 impl Format {
+    /// Guess the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| Self::from_str(&ext.to_ascii_lowercase()).ok())
+    }
+
     pub fn serialize_to_writer<W: Write>(
         self,
         mut writer: W,
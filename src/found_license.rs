@@ -1,12 +1,22 @@
 //! A FoundLicense represents the raw form of the found parts of a license for a given package.
 
-use cargo_metadata::{camino::Utf8PathBuf, Package};
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::Package;
 use thiserror::Error;
 
 use crate::{
-    discovery::{find_package_license, Confidence, LicenseText},
-    finalized_license::{FinalizedLicense, LicenseAndText, LICENSE_NOT_FOUNT_TEXT},
+    diagnostic::{Diagnostic, DiagnosticKind},
+    discovery::{
+        find_package_license, reuse_errors, scan_package_sources, Confidence, DiscoveryError,
+        DiscoveryOptions, LicenseText, ScanKind,
+    },
+    finalized_license::{AlternateText, FinalizedLicense, LicenseAndText, LICENSE_NOT_FOUNT_TEXT},
     license::License,
+    reuse::ReuseError,
 };
 
 #[derive(Debug, Error)]
@@ -25,16 +35,79 @@ struct FoundText {
     license: License,
     best_choice: BestChoice,
     confidence: Confidence,
+    /// The canonical license text to fall back on if no text was found.
+    canonical: Option<String>,
+    /// Chosen license files that match the template of another license better, with the license they match.
+    mismatches: Vec<(PathBuf, License)>,
 }
 
 impl FoundText {
-    fn new(license: License, best_choice: BestChoice, confidence: Confidence) -> Self {
+    fn new(
+        license: License,
+        best_choice: BestChoice,
+        confidence: Confidence,
+        options: &DiscoveryOptions,
+    ) -> Self {
+        let canonical = match best_choice {
+            BestChoice::None => options.canonical_text(&license),
+            _ => None,
+        };
+        let chosen = match &best_choice {
+            BestChoice::Single(lic_text) => std::slice::from_ref(lic_text),
+            BestChoice::Multiple(lic_texts) => lic_texts.as_slice(),
+            BestChoice::None => &[],
+        };
+        let mismatches = chosen
+            .iter()
+            .filter_map(|lic_text| {
+                let identified = lic_text.identified.as_deref()?.parse().ok()?;
+                Some((lic_text.path.clone(), identified))
+            })
+            .collect();
         Self {
             license,
             best_choice,
             confidence,
+            canonical,
+            mismatches,
         }
     }
+
+    fn to_license_and_text(&self) -> LicenseAndText {
+        let mut license_and_text = match &self.best_choice {
+            BestChoice::Single(lic_text) => {
+                LicenseAndText::new(&self.license, lic_text.text.clone())
+            }
+            BestChoice::Multiple(lic_texts) => {
+                LicenseAndText::new(&self.license, lic_texts[0].text.clone())
+            }
+            BestChoice::None => match &self.canonical {
+                Some(canonical) => LicenseAndText::canonical(&self.license, canonical.clone()),
+                None => LicenseAndText::new(&self.license, String::from(LICENSE_NOT_FOUNT_TEXT)),
+            },
+        };
+        let chosen = match &self.best_choice {
+            BestChoice::Single(lic_text) => Some(lic_text),
+            BestChoice::Multiple(lic_texts) => lic_texts.first(),
+            BestChoice::None => None,
+        };
+        if let Some(lic_text) = chosen {
+            license_and_text.source_path = Some(cargo_home_relative(&lic_text.path));
+            license_and_text.match_score = lic_text.match_score.map(round_score);
+        }
+        if let BestChoice::Multiple(lic_texts) = &self.best_choice {
+            license_and_text.alternates = lic_texts[1..]
+                .iter()
+                .map(|lic_text| AlternateText {
+                    source_path: Some(cargo_home_relative(&lic_text.path)),
+                    match_score: lic_text.match_score.map(round_score),
+                    text: lic_text.text.clone(),
+                })
+                .collect();
+        }
+        license_and_text.confidence = Some(self.confidence);
+        license_and_text
+    }
 }
 
 enum FoundTexts {
@@ -42,37 +115,60 @@ enum FoundTexts {
     Multiple(Vec<FoundText>),
 }
 
+/// Find the candidate texts for a license of a package, from the discovery cache if possible.
+fn find_texts(
+    package: &Package,
+    license: &License,
+    options: &DiscoveryOptions,
+) -> Result<Vec<LicenseText>, DiscoveryError> {
+    if let Some(texts) = options
+        .cache
+        .as_ref()
+        .and_then(|cache| cache.get(package, license))
+    {
+        return Ok(texts);
+    }
+    let texts = find_package_license(package, license, options)?;
+    if let Some(cache) = &options.cache {
+        cache.insert(package, license, &texts);
+    }
+    Ok(texts)
+}
+
 /// An [`FoundLicense`] represents a found set license texts for a given package
 /// along with the possible found texts.
 pub struct FoundLicense {
     package: Package,
     license: License,
     texts: FoundTexts,
+    /// Malformed REUSE data that was skipped while searching the package.
+    reuse_errors: Vec<(PathBuf, ReuseError)>,
 }
 
 impl FoundLicense {
     /// Search a package for a possible license and identify the best candidates.
-    pub fn new(package: &Package) -> Result<Self, FoundLicenseError> {
+    pub fn new(package: &Package, options: &DiscoveryOptions) -> Result<Self, FoundLicenseError> {
         let license = package.license();
         let texts = match &license {
             License::Unspecified => FoundTexts::Single(FoundText::new(
                 license.clone(),
                 BestChoice::None,
                 Confidence::UnspecifiedLicenseInPackage,
+                options,
             )),
             License::Multiple(licenses) => {
                 let mut choices = vec![];
                 for lic in licenses {
-                    let texts = find_package_license(package, lic)?;
+                    let texts = find_texts(package, lic, options)?;
                     let (choice, conf) = choose(texts);
-                    choices.push(FoundText::new(lic.clone(), choice, conf));
+                    choices.push(FoundText::new(lic.clone(), choice, conf, options));
                 }
                 FoundTexts::Multiple(choices)
             }
             license => {
-                let texts = find_package_license(package, license)?;
+                let texts = find_texts(package, license, options)?;
                 let (choice, conf) = choose(texts);
-                FoundTexts::Single(FoundText::new(license.clone(), choice, conf))
+                FoundTexts::Single(FoundText::new(license.clone(), choice, conf, options))
             }
         };
 
@@ -80,116 +176,202 @@ impl FoundLicense {
             package: package.clone(),
             license,
             texts,
+            reuse_errors: reuse_errors(package),
         })
     }
 
+    /// The license of the package.
+    pub fn license(&self) -> &License {
+        &self.license
+    }
+
     /// Check for any errors / issues with found licenses
-    pub fn check(&self) {
+    pub fn check(&self) -> Vec<Diagnostic> {
         // check if multiple possible licneses were found
         // check the confidense level of the best match
 
-        fn check_text(text: &FoundText, package: &Package) {
-            match &text.license {
-                License::Unspecified => {
-                    log::warn!(
+        fn check_text(text: &FoundText, package: &Package) -> Vec<Diagnostic> {
+            let license = &text.license;
+            let describe = |what: &str| {
+                format!(
+                    "{} for {} license in {}:{} - {}",
+                    what, license, package.name, package.version, package.manifest_path
+                )
+            };
+            let confidence = |paths: Vec<PathBuf>| {
+                let kind = DiagnosticKind::from_confidence(text.confidence)?;
+                let what = match kind {
+                    DiagnosticKind::SemiConfident => "Confidence level SEMI",
+                    DiagnosticKind::Unsure => "Confidence level UNSURE",
+                    DiagnosticKind::NoTemplate => "No template",
+                    _ => unimplemented!(),
+                };
+                Some(Diagnostic::new(
+                    package,
+                    license,
+                    kind,
+                    paths,
+                    describe(what),
+                ))
+            };
+            let (kind, paths, message) = match (license, &text.best_choice) {
+                (License::Unspecified, _) => (
+                    DiagnosticKind::Unspecified,
+                    vec![],
+                    format!(
                         "License is not specified for {}:{} in package: {}",
-                        package.name,
-                        package.version,
-                        package.manifest_path
-                    );
+                        package.name, package.version, package.manifest_path
+                    ),
+                ),
+                (_, BestChoice::Single(lic_text)) => {
+                    return confidence(vec![lic_text.path.clone()])
+                        .into_iter()
+                        .collect();
                 }
-                license => match text.best_choice {
-                    BestChoice::Single(_) => match text.confidence {
-                        Confidence::Confident => (),
-                        Confidence::SemiConfident => log::warn!(
-                            "Confidence level SEMI for {} license in {}:{} - {}",
-                            license,
-                            package.name,
-                            package.version,
-                            package.manifest_path
-                        ),
-                        Confidence::Unsure => log::warn!(
-                            "Confidence level UNSURE for {} license in {}:{} - {}",
-                            license,
-                            package.name,
-                            package.version,
-                            package.manifest_path
-                        ),
-                        Confidence::NoTemplate => log::warn!(
-                            "No template for {} license in {}:{} - {}",
-                            license,
-                            package.name,
-                            package.version,
-                            package.manifest_path
-                        ),
-                        _ => unimplemented!(),
-                    },
-                    BestChoice::Multiple(_) => {
-                        log::warn!(
-                            "Multiple possible licenses found for {} license in {}:{} - {}",
-                            license,
-                            package.name,
-                            package.version,
-                            package.manifest_path
-                        );
-                    }
-                    BestChoice::None => {
-                        log::warn!(
-                            "No license found for {} license in {}:{} - {}",
-                            license,
-                            package.name,
-                            package.version,
-                            package.manifest_path
-                        );
-                    }
-                },
+                (_, BestChoice::Multiple(lic_texts)) => {
+                    let paths: Vec<_> = lic_texts.iter().map(|t| t.path.clone()).collect();
+                    // The candidates are all as good as the best one, so its confidence is checked like a single file
+                    return std::iter::once(Diagnostic::new(
+                        package,
+                        license,
+                        DiagnosticKind::MultipleFiles,
+                        paths.clone(),
+                        describe("Multiple possible licenses found"),
+                    ))
+                    .chain(confidence(paths))
+                    .collect();
+                }
+                (_, BestChoice::None) if text.canonical.is_some() => (
+                    DiagnosticKind::Canonical,
+                    vec![],
+                    format!(
+                        "No license found for {} license in {}:{}, using canonical SPDX text - {}",
+                        license, package.name, package.version, package.manifest_path
+                    ),
+                ),
+                (_, BestChoice::None) => (
+                    DiagnosticKind::Missing,
+                    vec![],
+                    format!(
+                        "No license found for {} license in {}:{} - {}",
+                        license, package.name, package.version, package.manifest_path
+                    ),
+                ),
             };
+            vec![Diagnostic::new(package, license, kind, paths, message)]
         }
 
-        match &self.texts {
-            FoundTexts::Single(text) => check_text(text, &self.package),
-            FoundTexts::Multiple(texts) => {
-                for text in texts {
-                    check_text(text, &self.package);
+        fn check_mismatches<'a>(
+            text: &'a FoundText,
+            package: &'a Package,
+        ) -> impl Iterator<Item = Diagnostic> + 'a {
+            text.mismatches.iter().map(move |(path, identified)| {
+                let message = format!(
+                    "License file {} looks like {} rather than the declared {} license in {}:{} - {}",
+                    path.display(),
+                    identified,
+                    text.license,
+                    package.name,
+                    package.version,
+                    package.manifest_path
+                );
+                Diagnostic::new(
+                    package,
+                    &text.license,
+                    DiagnosticKind::LicenseMismatch,
+                    vec![path.clone()],
+                    message,
+                )
+            })
+        }
+
+        let texts = match &self.texts {
+            FoundTexts::Single(text) => std::slice::from_ref(text),
+            FoundTexts::Multiple(texts) => texts.as_slice(),
+        };
+        let mut diagnostics: Vec<Diagnostic> = texts
+            .iter()
+            .flat_map(|text| check_text(text, &self.package))
+            .collect();
+        diagnostics.extend(
+            texts
+                .iter()
+                .flat_map(|text| check_mismatches(text, &self.package)),
+        );
+        diagnostics.extend(self.reuse_errors.iter().map(|(dir, err)| {
+            Diagnostic::new(
+                &self.package,
+                &self.license,
+                DiagnosticKind::InvalidReuse,
+                vec![dir.clone()],
+                format!(
+                    "Ignoring REUSE data in {} for {}:{} - {}",
+                    dir.display(),
+                    self.package.name,
+                    self.package.version,
+                    err
+                ),
+            )
+        }));
+        diagnostics
+    }
+
+    /// Deep scan the package sources for any licenses that are not part of the declared license.
+    pub fn check_sources(&self, options: &DiscoveryOptions) -> Vec<Diagnostic> {
+        let declared = self.license.components();
+        let mut diagnostics = vec![];
+        for scanned in scan_package_sources(&self.package, options) {
+            let what = match scanned.kind {
+                ScanKind::NestedFile => "license file",
+                ScanKind::SpdxHeader => "SPDX header",
+                ScanKind::ReuseAnnotation => "REUSE annotation",
+            };
+            match &scanned.license {
+                Some(license) => {
+                    for lic in license.components() {
+                        if !declared.contains(&lic) {
+                            diagnostics.push(Diagnostic::new(
+                                &self.package,
+                                lic,
+                                DiagnosticKind::UndeclaredLicense,
+                                vec![scanned.path.clone()],
+                                format!(
+                                    "Undeclared {} license found in {} for {}:{} - {}",
+                                    lic,
+                                    what,
+                                    self.package.name,
+                                    self.package.version,
+                                    scanned.path.display()
+                                ),
+                            ));
+                        }
+                    }
                 }
+                None => diagnostics.push(Diagnostic::new(
+                    &self.package,
+                    &self.license,
+                    DiagnosticKind::UnidentifiedLicense,
+                    vec![scanned.path.clone()],
+                    format!(
+                        "Unidentified {} found for {}:{} - {}",
+                        what,
+                        self.package.name,
+                        self.package.version,
+                        scanned.path.display()
+                    ),
+                )),
             }
         }
+        diagnostics
     }
 
     /// Finalize license choices by picking the top choice if multiple are present and filling in not-found licenses with
     /// a signifier value.
     pub fn finalize(&self) -> FinalizedLicense {
-        let mut licenses = vec![];
-        match &self.texts {
-            FoundTexts::Single(text) => match &text.best_choice {
-                BestChoice::Single(lic_text) => {
-                    licenses.push(LicenseAndText::new(&text.license, lic_text.text.clone()))
-                }
-                BestChoice::Multiple(lic_texts) => licenses.push(LicenseAndText::new(
-                    &text.license,
-                    lic_texts[0].text.clone(),
-                )),
-                BestChoice::None => licenses.push(LicenseAndText::new(
-                    &text.license,
-                    String::from(LICENSE_NOT_FOUNT_TEXT),
-                )),
-            },
+        let licenses = match &self.texts {
+            FoundTexts::Single(text) => vec![text.to_license_and_text()],
             FoundTexts::Multiple(texts) => {
-                for text in texts {
-                    match &text.best_choice {
-                        BestChoice::Single(lic_text) => {
-                            licenses.push(LicenseAndText::new(&text.license, lic_text.text.clone()))
-                        }
-                        BestChoice::Multiple(lic_texts) => licenses.push(LicenseAndText::new(
-                            &text.license,
-                            lic_texts[0].text.clone(),
-                        )),
-                        BestChoice::None => licenses.push(LicenseAndText::new(
-                            &text.license,
-                            String::from(LICENSE_NOT_FOUNT_TEXT),
-                        )),
-                    }
-                }
+                texts.iter().map(FoundText::to_license_and_text).collect()
             }
         };
 
@@ -197,6 +379,22 @@ impl FoundLicense {
     }
 }
 
+/// Round a match score to keep the output stable across small changes of a text.
+fn round_score(score: f32) -> f32 {
+    (score * 1000.0).round() / 1000.0
+}
+
+/// Order candidates of the same confidence by best match score, then by path, so the choice does not depend on the
+/// order of the directory listing.
+fn rank(texts: &mut [LicenseText]) {
+    texts.sort_by(|a, b| {
+        b.match_score
+            .partial_cmp(&a.match_score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+}
+
 /// Choose the highest confidence license of all possible licenses.
 fn choose(texts: Vec<LicenseText>) -> (BestChoice, Confidence) {
     // Partition licnese texts by confidense
@@ -210,6 +408,15 @@ fn choose(texts: Vec<LicenseText>) -> (BestChoice, Confidence) {
         .into_iter()
         .partition(|text| text.confidence == Confidence::Unsure);
 
+    for group in [
+        &mut confident,
+        &mut semi_confident,
+        &mut unsure,
+        &mut no_template,
+    ] {
+        rank(group);
+    }
+
     if confident.len() == 1 {
         (
             BestChoice::Single(confident.swap_remove(0)),
@@ -246,6 +453,19 @@ fn choose(texts: Vec<LicenseText>) -> (BestChoice, Confidence) {
     }
 }
 
+/// If the path starts with the cargo_home path, strip it and replace with the ENV var $CARGO_HOME.
+/// This makes paths comparable across machines.
+pub fn cargo_home_relative(path: &Path) -> PathBuf {
+    match home::cargo_home()
+        .ok()
+        .and_then(|cargo_home| path.strip_prefix(cargo_home).ok())
+    {
+        Some(stripped) => Path::new("$CARGO_HOME").join(stripped),
+        // path is not under $CARGO_HOME keep it as is
+        None => path.to_path_buf(),
+    }
+}
+
 /// Extension trait get a [`License`].
 pub trait Licensed {
     fn license(&self) -> License;
@@ -259,20 +479,61 @@ impl Licensed for Package {
             .and_then(|license| license.parse::<License>().ok())
             .or_else(|| {
                 self.license_file().map(|license_file| {
-                    // If the license file starts with the cargo_home path, strip it and replace with the ENV var $CARGO_HOME.
-                    // This makes licenses comparable across machines.
-                    let license_file = if let Some(stripped_license_file) = home::cargo_home()
-                        .ok()
-                        .and_then(|cargo_home| license_file.strip_prefix(cargo_home).ok())
-                    {
-                        Utf8PathBuf::from("$CARGO_HOME").join(stripped_license_file)
-                    } else {
-                        // license file is not under $CARGO_HOME keep it as is
-                        license_file
-                    };
-                    License::File(license_file.into_std_path_buf())
+                    License::File(cargo_home_relative(license_file.as_std_path()))
                 })
             })
             .unwrap_or_default()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cargo_home_relative_paths() {
+        let cargo_home = home::cargo_home().unwrap();
+        assert_eq!(
+            cargo_home_relative(&cargo_home.join("registry/src/index/a-1.0.0/LICENSE")),
+            Path::new("$CARGO_HOME/registry/src/index/a-1.0.0/LICENSE")
+        );
+        assert_eq!(
+            cargo_home_relative(Path::new("/workspace/dep/LICENSE")),
+            Path::new("/workspace/dep/LICENSE")
+        );
+    }
+
+    #[test]
+    fn ties_are_broken_by_path() {
+        let text = |path: &str, score: f32| LicenseText {
+            path: PathBuf::from(path),
+            text: path.to_owned(),
+            confidence: Confidence::Confident,
+            match_score: Some(score),
+            identified: None,
+        };
+        let texts = vec![
+            text("b/LICENSE", 0.95),
+            text("LICENSE-MIT", 0.99),
+            text("a/LICENSE", 0.95),
+            text("COPYING", 0.95),
+        ];
+
+        for reversed in [false, true] {
+            let mut texts = texts.clone();
+            if reversed {
+                texts.reverse();
+            }
+            let paths: Vec<PathBuf> = match choose(texts) {
+                (BestChoice::Multiple(texts), Confidence::Confident) => {
+                    texts.into_iter().map(|text| text.path).collect()
+                }
+                _ => panic!("expected multiple confident texts"),
+            };
+            assert_eq!(
+                paths,
+                ["LICENSE-MIT", "COPYING", "a/LICENSE", "b/LICENSE"].map(PathBuf::from)
+            );
+        }
+    }
+}
@@ -5,10 +5,22 @@
     clippy::module_name_repetitions,
     clippy::must_use_candidate
 )]
+#[cfg(feature = "build")]
+pub mod build;
 pub mod bundle;
+pub mod cache;
+pub mod config;
+pub mod diagnostic;
 pub mod discovery;
 pub mod finalized_license;
 pub mod format;
 pub mod found_license;
 pub mod license;
+pub mod overrides;
 pub mod package_loader;
+pub mod policy;
+pub mod reuse;
+pub mod sarif;
+pub mod spdx_data;
+#[cfg(test)]
+mod test_util;
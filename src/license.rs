@@ -41,6 +41,11 @@ pub enum License {
     Unicode_3_0,
     UnicodeDFS2016,
     ISC,
+    /// Any other license of the SPDX License List, optionally with an exception.
+    Spdx {
+        id: &'static str,
+        exception: Option<&'static str>,
+    },
 
     // Special cases
     Custom(String),
@@ -50,31 +55,70 @@ pub enum License {
     Unspecified,
 }
 
+/// The bundled license templates.
+static TEMPLATES: &[(License, &str)] = &[
+    (License::Unlicense, include_str!("licenses/Unlicense")),
+    (License::MIT, include_str!("licenses/MIT")),
+    (License::Apache_2_0, include_str!("licenses/Apache-2.0")),
+    (
+        License::Apache_2_0_WITH_LLVM_exception,
+        include_str!("licenses/Apache-2.0_WITH_LLVM-exception"),
+    ),
+    (License::BSD_0_Clause, include_str!("licenses/0BSD")),
+    (License::BSD_2_Clause, include_str!("licenses/BSD-2-Clause")),
+    (License::BSD_3_Clause, include_str!("licenses/BSD-3-Clause")),
+    (License::BSL_1_0, include_str!("licenses/BSL-1.0")),
+    (
+        License::GPL_2_0Plus,
+        include_str!("licenses/GPL-2.0-or-later"),
+    ),
+    (
+        License::GPL_3_0Plus,
+        include_str!("licenses/GPL-3.0-or-later"),
+    ),
+    (
+        License::LGPL_2_1Plus,
+        include_str!("licenses/LGPL-2.1-or-later"),
+    ),
+    (
+        License::LGPL_3_0Plus,
+        include_str!("licenses/LGPL-3.0-or-later"),
+    ),
+    (License::Zlib, include_str!("licenses/Zlib")),
+    (License::Unicode_3_0, include_str!("licenses/Unicode-3.0")),
+    (
+        License::UnicodeDFS2016,
+        include_str!("licenses/Unicode-DFS-2016"),
+    ),
+    (License::ISC, include_str!("licenses/ISC")),
+    (License::MPL_2_0, include_str!("licenses/MPL-2.0")),
+];
+
 impl License {
     pub fn template(&self) -> Option<&'static str> {
-        Some(match *self {
-            License::Unlicense => include_str!("licenses/Unlicense"),
-            License::MIT => include_str!("licenses/MIT"),
-            License::Apache_2_0 => include_str!("licenses/Apache-2.0"),
-            License::Apache_2_0_WITH_LLVM_exception => {
-                include_str!("licenses/Apache-2.0_WITH_LLVM-exception")
-            }
-            License::BSD_0_Clause => include_str!("licenses/0BSD"),
-            License::BSD_2_Clause => include_str!("licenses/BSD-2-Clause"),
-            License::BSD_3_Clause => include_str!("licenses/BSD-3-Clause"),
-            License::BSL_1_0 => include_str!("licenses/BSL-1.0"),
-            License::GPL_2_0Plus => include_str!("licenses/GPL-2.0-or-later"),
-            License::GPL_3_0Plus => include_str!("licenses/GPL-3.0-or-later"),
-            License::LGPL_2_1Plus => include_str!("licenses/LGPL-2.1-or-later"),
-            License::LGPL_3_0Plus => include_str!("licenses/LGPL-3.0-or-later"),
-            License::Zlib => include_str!("licenses/Zlib"),
-            License::Unicode_3_0 => include_str!("licenses/Unicode-3.0"),
-            License::UnicodeDFS2016 => include_str!("licenses/Unicode-DFS-2016"),
-            License::ISC => include_str!("licenses/ISC"),
-            License::MPL_2_0 => include_str!("licenses/MPL-2.0"),
-            License::Multiple(_) => unimplemented!(), // This should be impossible to hit
-            _ => return None,
-        })
+        if let License::Multiple(_) = self {
+            unimplemented!() // This should be impossible to hit
+        }
+        TEMPLATES
+            .iter()
+            .find(|(license, _)| license == self)
+            .map(|(_, template)| *template)
+    }
+
+    /// All licenses that have a bundled template.
+    pub fn templated() -> Vec<License> {
+        TEMPLATES
+            .iter()
+            .map(|(license, _)| license.clone())
+            .collect()
+    }
+
+    /// The individual licenses that make up this license, i.e. each license of a [`License::Multiple`].
+    pub fn components(&self) -> Vec<&License> {
+        match self {
+            License::Multiple(licenses) => licenses.iter().collect(),
+            license => vec![license],
+        }
     }
 }
 
@@ -90,8 +134,80 @@ impl FromStr for License {
     }
 }
 
+/// Deprecated SPDX identifiers that are not GNU licenses, and their replacements.
+const DEPRECATED_REPLACEMENTS: &[(&str, &str, Option<&str>)] = &[
+    ("BSD-2-Clause-FreeBSD", "BSD-2-Clause", None),
+    ("BSD-2-Clause-NetBSD", "BSD-2-Clause", None),
+    ("Nunit", "zlib-acknowledgement", None),
+    ("StandardML-NJ", "SMLNJ", None),
+    ("bzip2-1.0.5", "bzip2-1.0.6", None),
+    (
+        "GPL-2.0-with-GCC-exception",
+        "GPL-2.0-only",
+        Some("GCC-exception-2.0"),
+    ),
+    (
+        "GPL-2.0-with-autoconf-exception",
+        "GPL-2.0-only",
+        Some("Autoconf-exception-2.0"),
+    ),
+    (
+        "GPL-2.0-with-bison-exception",
+        "GPL-2.0-only",
+        Some("Bison-exception-2.2"),
+    ),
+    (
+        "GPL-2.0-with-classpath-exception",
+        "GPL-2.0-only",
+        Some("Classpath-exception-2.0"),
+    ),
+    (
+        "GPL-2.0-with-font-exception",
+        "GPL-2.0-only",
+        Some("Font-exception-2.0"),
+    ),
+    (
+        "GPL-3.0-with-GCC-exception",
+        "GPL-3.0-only",
+        Some("GCC-exception-3.1"),
+    ),
+    (
+        "GPL-3.0-with-autoconf-exception",
+        "GPL-3.0-only",
+        Some("Autoconf-exception-3.0"),
+    ),
+    ("eCos-2.0", "GPL-2.0-or-later", Some("eCos-exception-2.0")),
+    (
+        "wxWindows",
+        "LGPL-2.0-or-later",
+        Some("WxWindows-exception-3.1"),
+    ),
+];
+
 fn simple_license(s: &str) -> License {
-    match s.trim() {
+    let s = s.trim();
+    if let Some(license) = named_license(s) {
+        return license;
+    }
+
+    // TODO: Sort out the SPDX "AND"
+    if s.contains('/') || s.contains(" OR ") {
+        let mut licenses = s
+            .split('/')
+            .flat_map(|s| s.split(" OR "))
+            .map(str::parse)
+            .map(Result::unwrap)
+            .collect::<Vec<License>>();
+        licenses.sort();
+        return License::Multiple(licenses);
+    }
+
+    spdx_license(s).unwrap_or_else(|| License::Custom(s.to_owned()))
+}
+
+/// Look up a license that has its own variant.
+fn named_license(s: &str) -> Option<License> {
+    Some(match s {
         "Unlicense" => License::Unlicense,
         "0BSD" => License::BSD_0_Clause,
         "CC0-1.0" => License::CC0_1_0,
@@ -119,19 +235,49 @@ fn simple_license(s: &str) -> License {
         "Unicode-3.0" => License::Unicode_3_0,
         "Unicode-DFS-2016" => License::UnicodeDFS2016,
         "ISC" => License::ISC,
-        // TODO: Sort out the SPDX "AND"
-        s if s.contains('/') || s.contains(" OR ") => {
-            let mut licenses = s
-                .split('/')
-                .flat_map(|s| s.split(" OR "))
-                .map(str::parse)
-                .map(Result::unwrap)
-                .collect::<Vec<License>>();
-            licenses.sort();
-            License::Multiple(licenses)
+        _ => return None,
+    })
+}
+
+/// Look up any license of the SPDX License List, replacing deprecated identifiers with their modern forms.
+fn spdx_license(s: &str) -> Option<License> {
+    let (license, exception) = match s.split_once(" WITH ") {
+        Some((license, exception)) => (license.trim(), Some(exception.trim())),
+        None => (s, None),
+    };
+    let mut exception = match exception {
+        Some(exception) => Some(spdx::exception_id(exception)?.name),
+        None => None,
+    };
+
+    let or_later = license.ends_with('+');
+    let mut id = spdx::license_id(license)?;
+    if let Some(&(_, replacement, replacement_exception)) = DEPRECATED_REPLACEMENTS
+        .iter()
+        .find(|(deprecated, _, _)| *deprecated == id.name)
+    {
+        if or_later || (replacement_exception.is_some() && exception.is_some()) {
+            return None;
         }
-        s => License::Custom(s.to_owned()),
+        id = spdx::license_id(replacement)?;
+        exception = exception.or(replacement_exception);
+    } else if id.is_gnu() {
+        if id.is_deprecated() || or_later {
+            id = spdx::gnu_license_id(id.name, or_later).unwrap_or(id);
+        }
+    } else if or_later {
+        // Only GNU licenses have an "or later" form in the license list
+        return None;
     }
+
+    let canonical = match exception {
+        Some(exception) => format!("{} WITH {}", id.name, exception),
+        None => id.name.to_owned(),
+    };
+    Some(named_license(&canonical).unwrap_or(License::Spdx {
+        id: id.name,
+        exception,
+    }))
 }
 
 fn process_spdx_expression(expr: spdx::Expression) -> License {
@@ -189,6 +335,10 @@ impl fmt::Display for License {
             License::Unicode_3_0 => write!(w, "Unicode-3.0"),
             License::UnicodeDFS2016 => write!(w, "Unicode-DFS-2016"),
             License::ISC => write!(w, "ISC"),
+            License::Spdx { id, exception } => match exception {
+                Some(exception) => write!(w, "{} WITH {}", id, exception),
+                None => write!(w, "{}", id),
+            },
             License::Custom(ref s) => write!(w, "{}", s),
             License::File(ref f) => {
                 write!(w, "License specified in file ({})", f.to_string_lossy())
@@ -304,6 +454,69 @@ mod test {
         );
     }
 
+    #[test]
+    fn full_spdx_list() {
+        assert_eq!(
+            License::from_str("MIT-0"),
+            Ok(License::Spdx {
+                id: "MIT-0",
+                exception: None
+            })
+        );
+        assert_eq!(
+            License::from_str("EPL-2.0"),
+            Ok(License::Spdx {
+                id: "EPL-2.0",
+                exception: None
+            })
+        );
+        assert_eq!(
+            License::from_str("GPL-2.0-only WITH Classpath-exception-2.0"),
+            Ok(License::Spdx {
+                id: "GPL-2.0-only",
+                exception: Some("Classpath-exception-2.0")
+            })
+        );
+        assert_eq!(
+            License::from_str("MIT-0").unwrap().to_string(),
+            String::from("MIT-0")
+        );
+        assert_eq!(
+            License::from_str("Not-A-License"),
+            Ok(License::Custom(String::from("Not-A-License")))
+        );
+    }
+
+    #[test]
+    fn deprecated_spdx() {
+        assert_eq!(License::from_str("GPL-2.0"), Ok(License::GPL_2_0));
+        assert_eq!(
+            License::from_str("LGPL-2.0+"),
+            Ok(License::Spdx {
+                id: "LGPL-2.0-or-later",
+                exception: None
+            })
+        );
+        assert_eq!(
+            License::from_str("GFDL-1.3"),
+            Ok(License::Spdx {
+                id: "GFDL-1.3-only",
+                exception: None
+            })
+        );
+        assert_eq!(
+            License::from_str("BSD-2-Clause-FreeBSD"),
+            Ok(License::BSD_2_Clause)
+        );
+        assert_eq!(
+            License::from_str("GPL-2.0-with-classpath-exception"),
+            Ok(License::Spdx {
+                id: "GPL-2.0-only",
+                exception: Some("Classpath-exception-2.0")
+            })
+        );
+    }
+
     #[test]
     fn complex_spdx() {
         assert_eq!(
@@ -323,4 +536,13 @@ mod test {
             ]))
         );
     }
+
+    #[test]
+    fn templated() {
+        let templated = License::templated();
+        assert_eq!(templated.len(), 17);
+        assert!(templated.iter().all(|license| license.template().is_some()));
+        assert!(License::GPL_2_0.template().is_none());
+        assert!(!templated.contains(&License::GPL_2_0));
+    }
 }
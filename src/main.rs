@@ -1,18 +1,31 @@
 use std::{
     env,
-    fs::File,
+    fs::{self, File},
     io::{self, BufReader, BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::exit,
 };
 
-use anyhow::{Error, Result};
-use bundle_licenses_lib::{bundle::BundleBuilder, format::Format};
-use clap::{self, Parser};
+use anyhow::{anyhow, Error, Result};
+use bundle_licenses_lib::{
+    bundle::{Bundle, BundleBuilder, Schema},
+    config::Config,
+    diagnostic::{Diagnostic, DiagnosticKind, DiagnosticsFormat},
+    discovery::{Confidence, DiscoveryOptions},
+    finalized_license::MetadataField,
+    format::Format,
+    spdx_data::SpdxData,
+};
+use clap::{self, Parser, Subcommand};
 use env_logger::Env;
 
 use git_version::git_version;
 
+mod cmd;
+#[cfg(test)]
+#[allow(dead_code)]
+mod test_util;
+
 pub const CARGO_BUNDLE_LICENSES_VERSION: &str = git_version!(
     cargo_prefix = "cargo:",
     prefix = "git:",
@@ -40,6 +53,28 @@ fn get_output(path: Option<PathBuf>) -> Result<Box<dyn Write + Send + 'static>>
     Ok(writer)
 }
 
+/// Write the bundle to a temporary file next to `path` and then move it into place.
+fn write_atomically(path: &Path, format: Format, bundle: &Bundle) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let write = || -> Result<()> {
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        format.serialize_to_writer(&mut writer, bundle)?;
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
 /// Check if err is a broken pipe.
 #[inline]
 fn is_broken_pipe(err: &Error) -> bool {
@@ -54,9 +89,13 @@ fn is_broken_pipe(err: &Error) -> bool {
 #[derive(Parser, Debug)]
 #[structopt(bin_name = "cargo bundle-licenses", author, version = CARGO_BUNDLE_LICENSES_VERSION)]
 pub struct Opts {
-    /// The format to write the output in
-    #[structopt(long, short, default_value = "toml", value_enum)]
-    format: Format,
+    /// The format to write the output in [default: toml]
+    #[structopt(long, short, value_enum)]
+    format: Option<Format>,
+
+    /// The schema to write the output in, v2 stores identical license texts once [default: the schema of the previous file, or v1]
+    #[structopt(long, value_enum)]
+    schema: Option<Schema>,
 
     /// The file to write the output to. None or "-" for STDOUT
     #[structopt(long, short)]
@@ -67,16 +106,227 @@ pub struct Opts {
     previous: Option<PathBuf>,
 
     /// After filling in not-found licenses, check if new is a strict subset of previous.
-    #[structopt(long, short)]
+    #[structopt(long, short, overrides_with = "no_check_previous")]
     check_previous: bool,
 
+    /// Don't check if new is a strict subset of previous, even if the config says so
+    #[structopt(long, overrides_with = "check_previous")]
+    no_check_previous: bool,
+
+    /// Carry texts from previous forward from older semver compatible versions of a package with an unchanged license, instead of only using texts of the exact same version
+    #[structopt(long, overrides_with = "no_carry_forward")]
+    carry_forward: bool,
+
+    /// Only use texts from previous for the exact same package version, even if the config sets carry-forward
+    #[structopt(
+        long,
+        visible_alias = "strict-versions",
+        overrides_with = "carry_forward"
+    )]
+    no_carry_forward: bool,
+
+    /// A config file to read options from, instead of looking for bundle-licenses.toml or [workspace.metadata.bundle-licenses]
+    #[structopt(long)]
+    config: Option<PathBuf>,
+
     /// A list of additional features to pull dependencies from (default features are always enabled)
     #[structopt(long, value_delimiter =',', value_parser = clap::builder::NonEmptyStringValueParser::new())]
     features: Vec<String>,
 
+    /// A list of target triples to limit dependencies to [default: all targets]
+    #[structopt(long = "target", value_delimiter =',', value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    targets: Vec<String>,
+
     /// A list of preferred licenses to use when multiple licenses are found
     #[structopt(long, value_delimiter =',', value_parser = clap::builder::NonEmptyStringValueParser::new())]
     prefer: Vec<String>,
+
+    /// A list of packages to leave out of the bundle
+    #[structopt(long, value_delimiter =',', value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    exclude: Vec<String>,
+
+    /// Scan all package sources for nested license files and SPDX-License-Identifier headers
+    #[structopt(long, overrides_with = "no_deep_scan")]
+    deep_scan: bool,
+
+    /// Don't scan package sources, even if the config enables deep-scan
+    #[structopt(long, overrides_with = "deep_scan")]
+    no_deep_scan: bool,
+
+    /// Additional license file names to search for, use `{id}` as a placeholder for the license, i.e. `NOTICE-{id}`
+    #[structopt(long, value_delimiter =',', value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    license_file_pattern: Vec<String>,
+
+    /// A local checkout of https://github.com/spdx/license-list-data to use for licenses without a bundled template
+    #[structopt(long)]
+    spdx_data: Option<PathBuf>,
+
+    /// A file of manual license overrides keyed by `crate@semver-range`
+    #[structopt(long)]
+    overrides: Option<PathBuf>,
+
+    /// The format to write diagnostics about found licenses in [default: log]
+    #[structopt(long, value_enum)]
+    diagnostics_format: Option<DiagnosticsFormat>,
+
+    /// The file to write diagnostics to, "-" for STDOUT [default: STDERR]
+    #[structopt(long)]
+    diagnostics_output: Option<PathBuf>,
+
+    /// Kinds of diagnostics that fail the run, i.e. `missing,unsure,no-template`
+    #[structopt(long, value_enum, value_delimiter = ',')]
+    fail_on: Vec<DiagnosticKind>,
+
+    /// Fail the run on license texts found with less than this confidence
+    #[structopt(long, value_enum)]
+    min_confidence: Option<Confidence>,
+
+    /// Packages to not report any diagnostics for, except for license policy violations
+    #[structopt(long, value_delimiter =',', value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    allow_warning: Vec<String>,
+
+    /// Record the source path, confidence and match score of each license text in the output
+    #[structopt(long, overrides_with = "no_match_details")]
+    match_details: bool,
+
+    /// Leave the match details of each license text out of the output, even if the config sets match-details
+    #[structopt(long, overrides_with = "match_details")]
+    no_match_details: bool,
+
+    /// A list of package metadata from the Cargo.toml to include for each package, for attribution pages
+    #[structopt(long, value_enum, value_delimiter = ',')]
+    metadata_fields: Vec<MetadataField>,
+
+    /// The number of threads to look for license files with [default: one per CPU]
+    #[structopt(long, short)]
+    jobs: Option<usize>,
+
+    /// Search every package for license files instead of reusing the texts found in registry packages by previous runs
+    #[structopt(long, overrides_with = "cache")]
+    no_cache: bool,
+
+    /// Reuse the texts found in registry packages by previous runs, even if the config sets no-cache
+    #[structopt(long, overrides_with = "no_cache")]
+    cache: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Regenerate an existing thirdparty file in place, keeping any manually added license texts
+    Update {
+        /// The thirdparty file to update [default: the configured output]. The format is taken from the file extension unless --format is given
+        file: Option<PathBuf>,
+    },
+    /// Fill in licenses whose text could not be found from a local file, a license template, or pasted text
+    Resolve {
+        /// The thirdparty file to resolve [default: the configured output]. The format is taken from the file extension unless --format is given
+        file: Option<PathBuf>,
+    },
+    /// Review licenses that matched several files, and pin the right text
+    Review {
+        /// The thirdparty file to review [default: the configured output]. The format is taken from the file extension unless --format is given
+        file: Option<PathBuf>,
+    },
+    /// Add a metadata block to a thirdparty file written by an older version, and convert it to --schema if given
+    Migrate {
+        /// The thirdparty file to migrate [default: the configured output]. The format is taken from the file extension unless --format is given
+        file: Option<PathBuf>,
+    },
+}
+
+impl Opts {
+    /// Load the config file and apply the command line options on top of it.
+    fn into_config(self) -> Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => Config::from_path(path)?,
+            None => Config::discover()?.unwrap_or_default(),
+        };
+
+        if self.format.is_some() {
+            config.format = self.format;
+        }
+        if self.schema.is_some() {
+            config.schema = self.schema;
+        }
+        if self.output.is_some() {
+            config.output = self.output;
+        }
+        if self.previous.is_some() {
+            config.previous = self.previous;
+        }
+        override_flag(
+            &mut config.check_previous,
+            self.check_previous,
+            self.no_check_previous,
+        );
+        override_flag(
+            &mut config.carry_forward,
+            self.carry_forward,
+            self.no_carry_forward,
+        );
+        if !self.features.is_empty() {
+            config.features = self.features;
+        }
+        if !self.targets.is_empty() {
+            config.targets = self.targets;
+        }
+        if !self.prefer.is_empty() {
+            config.prefer = self.prefer;
+        }
+        if !self.exclude.is_empty() {
+            config.exclude = self.exclude;
+        }
+        override_flag(&mut config.deep_scan, self.deep_scan, self.no_deep_scan);
+        if !self.license_file_pattern.is_empty() {
+            config.license_file_patterns = self.license_file_pattern;
+        }
+        if self.spdx_data.is_some() {
+            config.spdx_data = self.spdx_data;
+        }
+        if self.overrides.is_some() {
+            config.overrides = self.overrides;
+        }
+        if self.diagnostics_format.is_some() {
+            config.diagnostics_format = self.diagnostics_format;
+        }
+        if self.diagnostics_output.is_some() {
+            config.diagnostics_output = self.diagnostics_output;
+        }
+        if !self.fail_on.is_empty() {
+            config.fail_on = self.fail_on;
+        }
+        if self.min_confidence.is_some() {
+            config.min_confidence = self.min_confidence;
+        }
+        if !self.allow_warning.is_empty() {
+            config.allow_warnings = self.allow_warning;
+        }
+        override_flag(
+            &mut config.match_details,
+            self.match_details,
+            self.no_match_details,
+        );
+        if !self.metadata_fields.is_empty() {
+            config.metadata_fields = self.metadata_fields;
+        }
+        override_flag(&mut config.no_cache, self.no_cache, self.cache);
+        if self.jobs.is_some() {
+            config.jobs = self.jobs;
+        }
+        Ok(config)
+    }
+}
+
+/// Set a boolean option of the config if either its flag or its negating flag was given on the command line.
+fn override_flag(option: &mut bool, set: bool, unset: bool) {
+    if set {
+        *option = true;
+    } else if unset {
+        *option = false;
+    }
 }
 
 /// Parse args and set up logging / tracing
@@ -91,30 +341,187 @@ fn setup() -> Opts {
     Opts::parse_from(args)
 }
 
+/// Write out the diagnostics in the configured format, logging them as warnings by default.
+fn write_diagnostics(config: &Config, diagnostics: &[Diagnostic]) -> Result<()> {
+    let format = config.diagnostics_format.unwrap_or_default();
+    match (&config.diagnostics_output, format) {
+        (None, DiagnosticsFormat::Log) => {
+            for diagnostic in diagnostics {
+                if diagnostic.kind == DiagnosticKind::PolicyViolation {
+                    log::error!("{diagnostic}");
+                } else {
+                    log::warn!("{diagnostic}");
+                }
+            }
+        }
+        (None, format) => format.write(io::stderr().lock(), diagnostics)?,
+        (Some(path), format) => format.write(get_output(Some(path.clone()))?, diagnostics)?,
+    }
+    Ok(())
+}
+
+/// The generator recorded in the metadata of bundles.
+fn generator() -> String {
+    format!("cargo-bundle-licenses {CARGO_BUNDLE_LICENSES_VERSION}")
+}
+
+/// Log the diagnostics that fail the run, returning true if there are any.
+fn check_failures(config: &Config, diagnostics: &[Diagnostic]) -> bool {
+    let failures = diagnostics
+        .iter()
+        .filter(|diagnostic| config.fails_on(diagnostic))
+        .count();
+    if failures > 0 {
+        log::error!("Found {failures} license issue(s) that fail the run");
+    }
+    failures > 0
+}
+
+/// Read an existing thirdparty file, defaulting to the configured output, in the configured format or the format of its
+/// extension.
+fn read_thirdparty_file(
+    config: &Config,
+    path: Option<PathBuf>,
+) -> Result<(PathBuf, Format, Bundle)> {
+    let path = path
+        .or_else(|| config.output.clone())
+        .ok_or_else(|| anyhow!("No thirdparty file was given"))?;
+    let format = config
+        .format
+        .or_else(|| Format::from_path(&path))
+        .unwrap_or_default();
+    let bundle = format.deserialize_from_reader(BufReader::new(File::open(&path)?))?;
+    Ok((path, format, bundle))
+}
+
+/// Regenerate the thirdparty file at `path`, using it as the previous bundle, and print a summary of the changes.
+fn update(config: Config, path: Option<PathBuf>) -> Result<()> {
+    let (path, format, previous) = read_thirdparty_file(&config, path)?;
+
+    let (bundle, diagnostics) = BundleBuilder::from_config(&config)?
+        .generator(&generator())
+        .previous(&previous)
+        .exec()?;
+    write_diagnostics(&config, &diagnostics)?;
+    let schema = config.schema.unwrap_or(previous.schema());
+    write_atomically(&path, format, &bundle.in_schema(schema))?;
+
+    let diff = bundle.diff(&previous);
+    if diff.is_empty() {
+        println!("{} is up to date", path.display());
+    } else {
+        for (name, version) in &diff.added {
+            println!("Added {name}:{version}");
+        }
+        for (name, version) in &diff.removed {
+            println!("Removed {name}:{version}");
+        }
+        for (name, version) in &diff.changed {
+            println!("Changed {name}:{version}");
+        }
+        println!(
+            "Updated {}: {} added, {} removed, {} changed",
+            path.display(),
+            diff.added.len(),
+            diff.removed.len(),
+            diff.changed.len()
+        );
+    }
+
+    if check_failures(&config, &diagnostics) {
+        exit(1);
+    }
+    Ok(())
+}
+
+/// Review the licenses with alternate texts in the thirdparty file at `path` and write back the pinned choices.
+fn review(config: Config, path: Option<PathBuf>) -> Result<()> {
+    let (path, format, mut bundle) = read_thirdparty_file(&config, path)?;
+    let pinned = cmd::review::review(&mut bundle, io::stdin().lock(), io::stdout().lock())?;
+    if pinned > 0 {
+        let schema = config.schema.unwrap_or(bundle.schema());
+        write_atomically(&path, format, &bundle.in_schema(schema))?;
+    }
+    println!("Pinned {} license(s) in {}", pinned, path.display());
+    Ok(())
+}
+
+/// Resolve the licenses without a text in the thirdparty file at `path` and write back the new texts.
+fn resolve(config: Config, path: Option<PathBuf>) -> Result<()> {
+    let (path, format, mut bundle) = read_thirdparty_file(&config, path)?;
+    let options = DiscoveryOptions {
+        spdx_data: config.spdx_data.as_deref().map(SpdxData::new).transpose()?,
+        ..DiscoveryOptions::default()
+    };
+    let resolved = cmd::resolve::resolve(
+        &mut bundle,
+        &options,
+        io::stdin().lock(),
+        io::stdout().lock(),
+    )?;
+    if resolved > 0 {
+        let schema = config.schema.unwrap_or(bundle.schema());
+        write_atomically(&path, format, &bundle.in_schema(schema))?;
+    }
+    println!("Resolved {} license(s) in {}", resolved, path.display());
+    Ok(())
+}
+
+/// Add metadata to the thirdparty file at `path` if it has none, and write it back in the configured schema.
+fn migrate(config: Config, path: Option<PathBuf>) -> Result<()> {
+    let (path, format, mut bundle) = read_thirdparty_file(&config, path)?;
+    let migrated = bundle.migrate(&generator());
+    let schema = config.schema.unwrap_or(bundle.schema());
+    if !migrated && schema == bundle.schema() {
+        println!("{} is up to date", path.display());
+        return Ok(());
+    }
+    write_atomically(&path, format, &bundle.in_schema(schema))?;
+    println!(
+        "Migrated {} to schema version {}",
+        path.display(),
+        schema.version()
+    );
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let opts = setup();
-    let previous = if let Some(path) = opts.previous {
+    let mut opts = setup();
+    let command = opts.command.take();
+    let config = opts.into_config()?;
+    match command {
+        Some(Command::Update { file }) => return update(config, file),
+        Some(Command::Resolve { file }) => return resolve(config, file),
+        Some(Command::Review { file }) => return review(config, file),
+        Some(Command::Migrate { file }) => return migrate(config, file),
+        None => (),
+    }
+
+    let format = config.format.unwrap_or_default();
+    let previous = if let Some(path) = &config.previous {
         let reader = BufReader::new(File::open(path)?);
-        Some(opts.format.deserialize_from_reader(reader)?)
+        Some(format.deserialize_from_reader(reader)?)
     } else {
         None
     };
 
-    let mut bundle_builder = BundleBuilder::new()
-        .features(&opts.features)
-        .prefer(&opts.prefer);
+    let mut bundle_builder = BundleBuilder::from_config(&config)?.generator(&generator());
 
     if let Some(previous) = previous.as_ref() {
         bundle_builder = bundle_builder.previous(previous);
     }
 
-    let bundle = bundle_builder.exec()?;
+    let (bundle, diagnostics) = bundle_builder.exec()?;
+    write_diagnostics(&config, &diagnostics)?;
 
-    let output = get_output(opts.output)?;
+    let output = get_output(config.output.clone())?;
+    let schema = config
+        .schema
+        .or_else(|| previous.as_ref().map(Bundle::schema))
+        .unwrap_or_default();
 
-    if let Err(err) = opts
-        .format
-        .serialize_to_writer(output, &bundle)
+    if let Err(err) = format
+        .serialize_to_writer(output, &bundle.in_schema(schema))
         .map_err(Error::from)
     {
         if is_broken_pipe(&err) {
@@ -124,11 +531,47 @@ fn main() -> Result<()> {
     }
 
     if previous.is_some()
-        && opts.check_previous
+        && config.check_previous
         && !previous.as_ref().unwrap().check_subset(&bundle)
     {
         log::error!("Previous bundle does not match latest bundle.");
         exit(1);
     }
+
+    if check_failures(&config, &diagnostics) {
+        exit(1);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn command_line_overrides_config() {
+        let dir = TempDir::new("opts");
+        let path = dir.join("bundle-licenses.toml");
+        fs::write(&path, "deep-scan = true\nno-cache = true\n").unwrap();
+        let config = |args: &[&str]| {
+            let mut argv = vec!["cargo-bundle-licenses", "--config", path.to_str().unwrap()];
+            argv.extend(args);
+            Opts::parse_from(argv).into_config().unwrap()
+        };
+
+        let from_file = config(&[]);
+        assert!(from_file.deep_scan);
+        assert!(from_file.no_cache);
+        assert!(!from_file.check_previous);
+
+        let overridden = config(&["--no-deep-scan", "--cache", "--check-previous"]);
+        assert!(!overridden.deep_scan);
+        assert!(!overridden.no_cache);
+        assert!(overridden.check_previous);
+
+        // The last of a flag and its negation wins
+        assert!(config(&["--no-deep-scan", "--deep-scan"]).deep_scan);
+        assert!(!config(&["--carry-forward", "--strict-versions"]).carry_forward);
+    }
+}
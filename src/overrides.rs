@@ -0,0 +1,259 @@
+//! Manual license overrides for packages whose metadata or license files are missing or wrong.
+//!
+//! Overrides are keyed by `crate@semver-range`, so they keep applying as a dependency is upgraded:
+//!
+//! ```toml
+//! ["ring@>=0.16, <0.18"]
+//! license = "ISC AND MIT AND OpenSSL"
+//! texts = { ISC = "licenses/ring-ISC.txt", MIT = "licenses/ring-MIT.txt", OpenSSL = "licenses/ring-OpenSSL.txt" }
+//!
+//! ["foo"]
+//! text = "licenses/foo.txt"
+//! ```
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use cargo_metadata::semver::{Version, VersionReq};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{finalized_license::FinalizedLicense, license::License};
+
+#[derive(Debug, Error)]
+pub enum OverridesError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Unable to parse {path}: {source}")]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid version range in override {spec}: {source}")]
+    VersionReq {
+        spec: String,
+        source: cargo_metadata::semver::Error,
+    },
+    #[error("Unable to read license text {path} of override {spec}: {source}")]
+    Text {
+        spec: String,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// The license of a package to use instead of the discovered one.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LicenseOverride {
+    /// The license expression to use instead of the one in the Cargo.toml.
+    pub license: Option<String>,
+    /// A file with the text to use for every license of the package.
+    pub text: Option<PathBuf>,
+    /// Files with the text to use for each license of the package, keyed by SPDX id.
+    pub texts: BTreeMap<String, PathBuf>,
+}
+
+impl LicenseOverride {
+    /// Does this override provide the text of every component of a license
+    pub fn covers(&self, license: &License) -> bool {
+        self.text.is_some()
+            || license
+                .components()
+                .iter()
+                .all(|l| self.text_path(&l.to_string()).is_some())
+    }
+
+    /// The file with the text of a license, if overridden.
+    fn text_path(&self, license: &str) -> Option<&Path> {
+        let license = License::from_str(license).unwrap();
+        self.texts
+            .iter()
+            .find(|(id, _)| License::from_str(id).unwrap() == license)
+            .map(|(_, path)| path.as_path())
+            .or(self.text.as_deref())
+    }
+}
+
+/// A single override along with the packages it applies to.
+#[derive(Debug, Clone)]
+struct Entry {
+    spec: String,
+    name: String,
+    version: VersionReq,
+    license_override: LicenseOverride,
+}
+
+/// A set of license overrides keyed by `crate@semver-range`.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    entries: Vec<Entry>,
+}
+
+impl Overrides {
+    /// Read an overrides file, license text paths are relative to the file.
+    pub fn from_path(path: &Path) -> Result<Self, OverridesError> {
+        let contents = fs::read_to_string(path)?;
+        let overrides = Self::from_str(&contents).map_err(|err| match err {
+            OverridesError::Toml { source, .. } => OverridesError::Toml {
+                path: path.to_path_buf(),
+                source,
+            },
+            err => err,
+        })?;
+        Ok(overrides.relative_to(path.parent().unwrap_or_else(|| Path::new(""))))
+    }
+
+    fn relative_to(mut self, dir: &Path) -> Self {
+        for entry in &mut self.entries {
+            let license_override = &mut entry.license_override;
+            for path in license_override
+                .text
+                .iter_mut()
+                .chain(license_override.texts.values_mut())
+            {
+                if path.is_relative() {
+                    *path = dir.join(&*path);
+                }
+            }
+        }
+        self
+    }
+
+    /// Find the override for a package version, returning its `crate@semver-range` key along with it.
+    pub fn find(&self, name: &str, version: &Version) -> Option<(&str, &LicenseOverride)> {
+        let mut matching = self
+            .entries
+            .iter()
+            .filter(|entry| entry.name == name && entry.version.matches(version));
+        let entry = matching.next()?;
+        if let Some(other) = matching.next() {
+            log::warn!(
+                "Multiple overrides match {}:{}, using {} over {}",
+                name,
+                version,
+                entry.spec,
+                other.spec
+            );
+        }
+        Some((&entry.spec, &entry.license_override))
+    }
+
+    /// Replace the license texts of a finalized license with any overridden texts and record the override.
+    pub fn apply_texts(
+        spec: &str,
+        license_override: &LicenseOverride,
+        library: &mut FinalizedLicense,
+    ) -> Result<(), OverridesError> {
+        for license in &mut library.licenses {
+            if let Some(path) = license_override.text_path(&license.license) {
+                license.text = fs::read_to_string(path).map_err(|source| OverridesError::Text {
+                    spec: spec.to_owned(),
+                    path: path.to_path_buf(),
+                    source,
+                })?;
+                license.canonical = false;
+                license.clear_match_details();
+            }
+        }
+        library.license_override = Some(spec.to_owned());
+        Ok(())
+    }
+}
+
+impl FromStr for Overrides {
+    type Err = OverridesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let table: BTreeMap<String, LicenseOverride> =
+            toml::from_str(s).map_err(|source| OverridesError::Toml {
+                path: PathBuf::new(),
+                source,
+            })?;
+
+        let entries = table
+            .into_iter()
+            .map(|(spec, license_override)| {
+                let (name, version) = match spec.split_once('@') {
+                    Some((name, version)) => (name.trim(), version.trim()),
+                    None => (spec.trim(), "*"),
+                };
+                let version =
+                    VersionReq::parse(version).map_err(|source| OverridesError::VersionReq {
+                        spec: spec.clone(),
+                        source,
+                    })?;
+                Ok(Entry {
+                    name: name.to_owned(),
+                    spec,
+                    version,
+                    license_override,
+                })
+            })
+            .collect::<Result<Vec<_>, OverridesError>>()?;
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_by_version_range() {
+        let overrides = Overrides::from_str(
+            r#"
+            ["ring@>=0.16, <0.18"]
+            license = "ISC AND MIT AND OpenSSL"
+
+            ["foo"]
+            text = "foo.txt"
+            "#,
+        )
+        .unwrap();
+
+        let (spec, ring) = overrides
+            .find("ring", &Version::parse("0.17.8").unwrap())
+            .unwrap();
+        assert_eq!(spec, "ring@>=0.16, <0.18");
+        assert_eq!(ring.license.as_deref(), Some("ISC AND MIT AND OpenSSL"));
+        assert!(!ring.covers(&License::MIT));
+        assert!(overrides
+            .find("ring", &Version::parse("0.18.0").unwrap())
+            .is_none());
+        assert!(overrides
+            .find("foo", &Version::parse("3.1.4").unwrap())
+            .is_some_and(|(_, foo)| foo.covers(&License::MIT)));
+        assert!(overrides
+            .find("bar", &Version::parse("1.0.0").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_version_range() {
+        assert!(matches!(
+            Overrides::from_str("[\"ring@not-a-version\"]\nlicense = \"MIT\""),
+            Err(OverridesError::VersionReq { .. })
+        ));
+    }
+
+    #[test]
+    fn text_per_license() {
+        let license_override = LicenseOverride {
+            license: None,
+            text: Some(PathBuf::from("all.txt")),
+            texts: BTreeMap::from([(String::from("MIT"), PathBuf::from("mit.txt"))]),
+        };
+        assert_eq!(
+            license_override.text_path("MIT"),
+            Some(Path::new("mit.txt"))
+        );
+        assert_eq!(
+            license_override.text_path("Apache-2.0"),
+            Some(Path::new("all.txt"))
+        );
+    }
+}
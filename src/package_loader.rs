@@ -1,6 +1,9 @@
 //! This module is responsible for parsing collecting all the packages in a project
 
-use std::collections::{HashSet, VecDeque};
+use std::{
+    collections::{HashSet, VecDeque},
+    path::Path,
+};
 
 use cargo_metadata::{
     CargoOpt, DependencyKind, Metadata, MetadataCommand, NodeDep, Package, PackageId, Resolve,
@@ -26,15 +29,41 @@ pub struct PackageLoader {
 }
 
 impl PackageLoader {
-    /// Create a new package loader that loads the cargo metadata
-    pub fn new(features: &[String]) -> Result<Self, PackageLoaderError> {
-        let metadata = MetadataCommand::new()
+    /// Create a new package loader that loads the cargo metadata, only including dependencies of the given targets if any.
+    ///
+    /// The manifest in the current directory is used unless a `manifest_path` is given.
+    pub fn new(
+        features: &[String],
+        targets: &[String],
+        manifest_path: Option<&Path>,
+    ) -> Result<Self, PackageLoaderError> {
+        let mut command = MetadataCommand::new();
+        if let Some(manifest_path) = manifest_path {
+            command.manifest_path(manifest_path);
+        }
+        let metadata = command
             .features(CargoOpt::SomeFeatures(features.to_vec()))
+            .other_options(
+                targets
+                    .iter()
+                    .flat_map(|target| [String::from("--filter-platform"), target.clone()])
+                    .collect::<Vec<_>>(),
+            )
             .exec()?;
 
         Ok(Self { metadata })
     }
 
+    /// The root directory of the workspace
+    pub fn workspace_root(&self) -> &Path {
+        self.metadata.workspace_root.as_std_path()
+    }
+
+    /// The target directory of the workspace
+    pub fn target_directory(&self) -> &Path {
+        self.metadata.target_directory.as_std_path()
+    }
+
     /// Get the top level packages for this project
     pub fn get_package_roots(&self) -> Result<Vec<&Package>, PackageLoaderError> {
         let resolve = self
@@ -0,0 +1,138 @@
+//! A license policy of allowed and denied licenses that a bundle can be checked against.
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use spdx::ParseMode;
+
+use crate::{bundle::Bundle, finalized_license::FinalizedLicense, license::License};
+
+/// Licenses that are allowed or denied for third party libraries.
+///
+/// A license is acceptable if it is not denied and either no licenses are explicitly allowed or it is one of them.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// Licenses that are allowed, if empty every license that is not denied is allowed.
+    pub allow: Vec<String>,
+    /// Licenses that are never allowed.
+    pub deny: Vec<String>,
+}
+
+/// A third party library whose license does not satisfy the [`Policy`].
+#[derive(Debug, Clone)]
+pub struct PolicyViolation {
+    pub package_name: String,
+    pub package_version: String,
+    pub license: String,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Is a single license acceptable under this policy
+    pub fn accepts(&self, license: &License) -> bool {
+        let matches = |ids: &[String]| {
+            ids.iter()
+                .any(|id| &License::from_str(id).unwrap() == license)
+        };
+        !matches(&self.deny) && (self.allow.is_empty() || matches(&self.allow))
+    }
+
+    /// Is the license of a library acceptable, taking `AND` and `OR` of the license expression into account.
+    pub fn accepts_library(&self, library: &FinalizedLicense) -> bool {
+        if let Ok(expr) = spdx::Expression::parse_mode(&library.license, ParseMode::LAX) {
+            // Licenses dropped in favor of a preferred license are no longer an option
+            let kept = |license: &License| {
+                library
+                    .licenses
+                    .iter()
+                    .any(|l| &License::from_str(&l.license).unwrap() == license)
+            };
+            expr.evaluate(|req| {
+                let license = License::from_str(&req.to_string()).unwrap();
+                kept(&license) && self.accepts(&license)
+            })
+        } else {
+            library
+                .licenses
+                .iter()
+                .any(|l| self.accepts(&License::from_str(&l.license).unwrap()))
+        }
+    }
+
+    /// Find all libraries in the bundle whose license is not acceptable.
+    pub fn violations(&self, bundle: &Bundle) -> Vec<PolicyViolation> {
+        if self.is_empty() {
+            return vec![];
+        }
+        bundle
+            .third_party_libraries()
+            .iter()
+            .filter(|library| !self.accepts_library(library))
+            .map(|library| PolicyViolation {
+                package_name: library.package_name.clone(),
+                package_version: library.package_version.clone(),
+                license: library.license.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::finalized_license::LicenseAndText;
+
+    fn library(license: &str, licenses: &[License]) -> FinalizedLicense {
+        FinalizedLicense {
+            package_name: String::from("example"),
+            package_version: String::from("1.0.0"),
+            repository: String::new(),
+            source: None,
+            authors: vec![],
+            homepage: None,
+            description: None,
+            license: String::from(license),
+            licenses: licenses
+                .iter()
+                .map(|l| LicenseAndText::new(l, String::new()))
+                .collect(),
+            license_override: None,
+        }
+    }
+
+    #[test]
+    fn deny() {
+        let policy = Policy {
+            allow: vec![],
+            deny: vec![String::from("GPL-3.0-only")],
+        };
+        assert!(policy.accepts_library(&library("MIT", &[License::MIT])));
+        assert!(!policy.accepts_library(&library("GPL-3.0", &[License::GPL_3_0])));
+        assert!(policy.accepts_library(&library(
+            "MIT OR GPL-3.0-only",
+            &[License::MIT, License::GPL_3_0]
+        )));
+        assert!(!policy.accepts_library(&library(
+            "MIT AND GPL-3.0-only",
+            &[License::MIT, License::GPL_3_0]
+        )));
+    }
+
+    #[test]
+    fn allow() {
+        let policy = Policy {
+            allow: vec![String::from("MIT"), String::from("Apache-2.0")],
+            deny: vec![],
+        };
+        assert!(policy.accepts_library(&library(
+            "MIT/Apache-2.0",
+            &[License::Apache_2_0, License::MIT]
+        )));
+        assert!(!policy.accepts_library(&library("ISC", &[License::ISC])));
+        // Only the preferred license is left to choose from
+        assert!(!policy.accepts_library(&library("MIT OR ISC", &[License::ISC])));
+    }
+}
@@ -0,0 +1,188 @@
+//! Support for projects following the [REUSE](https://reuse.software/spec/) specification.
+//!
+//! REUSE projects keep the text of every license they use in `LICENSES/<SPDX-ID>.txt` and map
+//! files to licenses in either `.reuse/dep5` or `REUSE.toml`.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::license::License;
+
+const LICENSES_DIR: &str = "LICENSES";
+const DEP5_FILE: &str = ".reuse/dep5";
+const REUSE_TOML_FILE: &str = "REUSE.toml";
+
+#[derive(Debug, Error)]
+pub enum ReuseError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Unable to parse {path}: {source}")]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
+
+/// The REUSE information of a single directory.
+#[derive(Debug)]
+pub struct Reuse {
+    root: PathBuf,
+    /// The licenses named by `.reuse/dep5` and `REUSE.toml`, along with the file naming them.
+    annotations: Vec<(PathBuf, License)>,
+}
+
+impl Reuse {
+    /// Load the REUSE information from a directory, if it follows the REUSE layout.
+    pub fn load(dir: &Path) -> Result<Option<Self>, ReuseError> {
+        let dep5 = dir.join(DEP5_FILE);
+        let reuse_toml = dir.join(REUSE_TOML_FILE);
+        if !dir.join(LICENSES_DIR).is_dir() && !dep5.is_file() && !reuse_toml.is_file() {
+            return Ok(None);
+        }
+
+        let mut annotated = vec![];
+        if dep5.is_file() {
+            let licenses = parse_dep5(&fs::read_to_string(&dep5)?);
+            annotated.push((dep5, licenses));
+        }
+        if reuse_toml.is_file() {
+            let licenses =
+                parse_reuse_toml(&fs::read_to_string(&reuse_toml)?).map_err(|source| {
+                    ReuseError::Toml {
+                        path: reuse_toml.clone(),
+                        source,
+                    }
+                })?;
+            annotated.push((reuse_toml, licenses));
+        }
+
+        let mut annotations: Vec<(PathBuf, License)> = vec![];
+        for (path, licenses) in annotated {
+            for license in licenses {
+                if !annotations.iter().any(|(p, l)| p == &path && l == &license) {
+                    annotations.push((path.clone(), license));
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            root: dir.to_path_buf(),
+            annotations,
+        }))
+    }
+
+    /// The licenses named in the `.reuse/dep5` and `REUSE.toml` annotations, along with the file naming them.
+    pub fn annotations(&self) -> &[(PathBuf, License)] {
+        &self.annotations
+    }
+
+    /// Read the exact text of a license from `LICENSES/<SPDX-ID>.txt`.
+    ///
+    /// A license with an exception is looked up as the license text followed by the exception text.
+    pub fn license_text(&self, license: &License) -> Option<(PathBuf, String)> {
+        let id = license.to_string();
+        let mut path = None;
+        let mut text = String::new();
+        for part in id.split(" WITH ") {
+            let part_path = self.root.join(LICENSES_DIR).join(format!("{}.txt", part));
+            let part_text = fs::read_to_string(&part_path).ok()?;
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&part_text);
+            path.get_or_insert(part_path);
+        }
+        path.map(|path| (path, text))
+    }
+}
+
+/// Collect the licenses of every `License:` field of a [dep5](https://dep-team.pages.debian.net/deps/dep5/) file.
+fn parse_dep5(contents: &str) -> Vec<License> {
+    contents
+        .lines()
+        .filter_map(|line| line.strip_prefix("License:"))
+        .map(str::trim)
+        .filter(|expr| !expr.is_empty())
+        .flat_map(components)
+        .collect()
+}
+
+/// Collect the licenses of every `SPDX-License-Identifier` of the annotations in a `REUSE.toml` file.
+fn parse_reuse_toml(contents: &str) -> Result<Vec<License>, toml::de::Error> {
+    let value: toml::Value = toml::from_str(contents)?;
+    let annotations = value
+        .get("annotations")
+        .and_then(toml::Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(annotations
+        .iter()
+        .filter_map(|annotation| annotation.get("SPDX-License-Identifier"))
+        .flat_map(|ids| match ids {
+            toml::Value::String(id) => vec![id.clone()],
+            toml::Value::Array(ids) => ids
+                .iter()
+                .filter_map(toml::Value::as_str)
+                .map(String::from)
+                .collect(),
+            _ => vec![],
+        })
+        .flat_map(|expr| components(&expr))
+        .collect())
+}
+
+/// The individual licenses of a license expression.
+fn components(expr: &str) -> Vec<License> {
+    expr.parse::<License>()
+        .unwrap()
+        .components()
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dep5() {
+        let dep5 = "Format: https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/\n\
+                    Upstream-Name: example\n\
+                    \n\
+                    Files: *\n\
+                    Copyright: 2024 Jane Doe\n\
+                    License: MIT OR Apache-2.0\n\
+                    \n\
+                    Files: vendor/*\n\
+                    License: BSD-3-Clause\n";
+        assert_eq!(
+            parse_dep5(dep5),
+            vec![License::MIT, License::Apache_2_0, License::BSD_3_Clause]
+        );
+    }
+
+    #[test]
+    fn reuse_toml() {
+        let reuse = r#"
+            version = 1
+
+            [[annotations]]
+            path = "src/**"
+            SPDX-FileCopyrightText = "2024 Jane Doe"
+            SPDX-License-Identifier = "MIT"
+
+            [[annotations]]
+            path = "vendor/**"
+            SPDX-License-Identifier = ["Zlib", "ISC"]
+        "#;
+        assert_eq!(
+            parse_reuse_toml(reuse).unwrap(),
+            vec![License::MIT, License::Zlib, License::ISC]
+        );
+    }
+}
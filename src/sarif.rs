@@ -0,0 +1,264 @@
+//! Convert [`Diagnostic`]s to a [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html) log
+//! so license issues can be shown in code scanning dashboards.
+use std::{collections::BTreeSet, fmt::Write, path::Path};
+
+use serde_json::{json, Value};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const INFORMATION_URI: &str = "https://github.com/sstadick/cargo-bundle-licenses";
+
+/// The SARIF level of a kind of diagnostic, following the confidence in the license text.
+fn level(kind: DiagnosticKind) -> &'static str {
+    match kind {
+        DiagnosticKind::SemiConfident => "note",
+        DiagnosticKind::Unsure
+        | DiagnosticKind::NoTemplate
+        | DiagnosticKind::MultipleFiles
+        | DiagnosticKind::Canonical
+        | DiagnosticKind::UnidentifiedLicense
+        | DiagnosticKind::InvalidReuse => "warning",
+        DiagnosticKind::Missing
+        | DiagnosticKind::Unspecified
+        | DiagnosticKind::UndeclaredLicense
+        | DiagnosticKind::PolicyViolation
+        | DiagnosticKind::LicenseMismatch => "error",
+    }
+}
+
+fn description(kind: DiagnosticKind) -> &'static str {
+    match kind {
+        DiagnosticKind::SemiConfident => "License text only partially matches its license",
+        DiagnosticKind::Unsure => "License text barely matches its license",
+        DiagnosticKind::NoTemplate => "No template to check the license text against",
+        DiagnosticKind::MultipleFiles => "Multiple possible license files found",
+        DiagnosticKind::Missing => "License text not found",
+        DiagnosticKind::Canonical => "License text not found, using the canonical SPDX text",
+        DiagnosticKind::Unspecified => "License not specified",
+        DiagnosticKind::UndeclaredLicense => "Undeclared license found in package sources",
+        DiagnosticKind::UnidentifiedLicense => "Unidentified license found in package sources",
+        DiagnosticKind::PolicyViolation => "License not allowed by the license policy",
+        DiagnosticKind::LicenseMismatch => "License file does not match the declared license",
+        DiagnosticKind::InvalidReuse => "REUSE data could not be read and is ignored",
+    }
+}
+
+/// The base id that URIs of files in the workspace are relative to.
+const SRCROOT: &str = "%SRCROOT%";
+
+/// A path with forward slashes, percent-encoded for use in a URI following RFC 3986.
+///
+/// Colons are only kept in absolute paths, in the first segment of a relative reference they would start a scheme.
+fn encode_path(path: &Path, absolute: bool) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'/'
+            | b'!'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b'+'
+            | b','
+            | b';'
+            | b'='
+            | b'@' => encoded.push(byte as char),
+            b':' if absolute => encoded.push(':'),
+            byte => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+/// A `file://` URI for an absolute path.
+fn file_uri(path: &Path) -> String {
+    let path = encode_path(path, true);
+    if path.starts_with('/') {
+        format!("file://{path}")
+    } else {
+        format!("file:///{path}")
+    }
+}
+
+/// A location relative to the workspace root if the path is in it, so code scanning can map it to a repository file.
+fn location(path: &Path, line: Option<usize>, root: Option<&Path>) -> Value {
+    let artifact = match root.and_then(|root| path.strip_prefix(root).ok()) {
+        Some(relative) => json!({ "uri": encode_path(relative, false), "uriBaseId": SRCROOT }),
+        None => json!({ "uri": file_uri(path) }),
+    };
+    let mut physical = json!({ "artifactLocation": artifact });
+    if let Some(line) = line {
+        physical["region"] = json!({ "startLine": line });
+    }
+    json!({ "physicalLocation": physical })
+}
+
+/// Create a SARIF log with a result for each diagnostic.
+///
+/// Results point at the line of the package in the workspace `Cargo.lock` if it is known, or at the manifest of the
+/// package otherwise. Files in the workspace, the directory of the `Cargo.lock`, are relative to `%SRCROOT%`.
+pub fn to_sarif(diagnostics: &[Diagnostic]) -> Value {
+    let root = diagnostics
+        .iter()
+        .find_map(|d| d.lockfile.as_ref())
+        .and_then(|lockfile| lockfile.path.parent());
+
+    let kinds: Vec<DiagnosticKind> = diagnostics
+        .iter()
+        .map(|d| d.kind)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let rules: Vec<Value> = kinds
+        .iter()
+        .map(|&kind| {
+            json!({
+                "id": kind.to_string(),
+                "shortDescription": { "text": description(kind) },
+                "defaultConfiguration": { "level": level(kind) },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let primary = match &diagnostic.lockfile {
+                Some(lockfile) => location(&lockfile.path, Some(lockfile.line), root),
+                None => location(&diagnostic.manifest_path, None, root),
+            };
+            let related: Vec<Value> = diagnostic
+                .paths
+                .iter()
+                .map(|path| location(path, None, root))
+                .collect();
+            let mut result = json!({
+                "ruleId": diagnostic.kind.to_string(),
+                "ruleIndex": kinds.iter().position(|&k| k == diagnostic.kind),
+                "level": level(diagnostic.kind),
+                "message": { "text": diagnostic.message },
+                "locations": [primary],
+                "partialFingerprints": {
+                    "package/v1": format!(
+                        "{}:{}:{}",
+                        diagnostic.package_name, diagnostic.package_version, diagnostic.license
+                    ),
+                },
+                "properties": {
+                    "package": diagnostic.package_name,
+                    "version": diagnostic.package_version,
+                    "license": diagnostic.license,
+                },
+            });
+            if !related.is_empty() {
+                result["relatedLocations"] = Value::Array(related);
+            }
+            result
+        })
+        .collect();
+
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+                "informationUri": INFORMATION_URI,
+                "rules": rules,
+            },
+        },
+        "results": results,
+    });
+    if let Some(root) = root {
+        // A base URI must end in a slash for relative URIs to resolve inside of it
+        let uri = format!("{}/", file_uri(root).trim_end_matches('/'));
+        run["originalUriBaseIds"] = json!({ SRCROOT: { "uri": uri } });
+    }
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [run],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::diagnostic::LockfileLocation;
+
+    fn diagnostic(kind: DiagnosticKind, lockfile: Option<LockfileLocation>) -> Diagnostic {
+        Diagnostic {
+            package_name: "dep".to_owned(),
+            package_version: "0.1.0".to_owned(),
+            license: "MIT".to_owned(),
+            kind,
+            paths: vec![],
+            manifest_path: PathBuf::from("/registry/dep-0.1.0/Cargo.toml"),
+            lockfile,
+            message: "message".to_owned(),
+        }
+    }
+
+    #[test]
+    fn sarif() {
+        let lockfile = LockfileLocation {
+            path: PathBuf::from("/workspace/Cargo.lock"),
+            line: 12,
+        };
+        let mut missing = diagnostic(DiagnosticKind::Missing, Some(lockfile));
+        missing.paths = vec![PathBuf::from("/workspace/vendor/dep #1/LICENSE-ü%")];
+        let sarif = to_sarif(&[missing, diagnostic(DiagnosticKind::Unsure, None)]);
+
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "unsure");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "missing");
+        assert_eq!(
+            run["originalUriBaseIds"]["%SRCROOT%"]["uri"],
+            "file:///workspace/"
+        );
+
+        let missing = &run["results"][0];
+        assert_eq!(missing["ruleIndex"], 1);
+        assert_eq!(missing["level"], "error");
+        let location = &missing["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "Cargo.lock");
+        assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+        assert_eq!(location["region"]["startLine"], 12);
+        assert_eq!(
+            missing["relatedLocations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "vendor/dep%20%231/LICENSE-%C3%BC%25"
+        );
+
+        // Files outside of the workspace keep an absolute URI
+        let unsure = &run["results"][1];
+        assert_eq!(unsure["level"], "warning");
+        let artifact = &unsure["locations"][0]["physicalLocation"]["artifactLocation"];
+        assert_eq!(artifact["uri"], "file:///registry/dep-0.1.0/Cargo.toml");
+        assert!(artifact.get("uriBaseId").is_none());
+    }
+
+    #[test]
+    fn encoded_paths() {
+        assert_eq!(
+            file_uri(Path::new("C:\\Users\\a b\\Cargo.lock")),
+            "file:///C:/Users/a%20b/Cargo.lock"
+        );
+        assert_eq!(encode_path(Path::new("a:b/c+d"), false), "a%3Ab/c+d");
+    }
+}
@@ -0,0 +1,138 @@
+//! License texts from a local checkout of [spdx/license-list-data](https://github.com/spdx/license-list-data).
+//!
+//! Only a handful of license templates are bundled with this crate, the license list data fills in the rest,
+//! both as templates to match found license files against and as the canonical text of licenses that
+//! could not be found in a package at all.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::license::License;
+
+/// The directory of the license list data holding the plain text of each license and exception.
+const TEXT_DIR: &str = "text";
+/// The license list, which changes with every release of the data.
+pub(crate) const LICENSE_LIST: &str = "json/licenses.json";
+
+#[derive(Debug, Error)]
+pub enum SpdxDataError {
+    #[error("{0} is not a checkout of spdx/license-list-data, no {TEXT_DIR} directory found")]
+    MissingTextDir(PathBuf),
+}
+
+/// A local checkout of the SPDX license list data.
+#[derive(Debug, Clone)]
+pub struct SpdxData {
+    text_dir: PathBuf,
+    /// The texts read so far, shared by all clones.
+    texts: Arc<RwLock<HashMap<License, Option<String>>>>,
+}
+
+impl SpdxData {
+    pub fn new(dir: &Path) -> Result<Self, SpdxDataError> {
+        let text_dir = dir.join(TEXT_DIR);
+        if !text_dir.is_dir() {
+            return Err(SpdxDataError::MissingTextDir(dir.to_path_buf()));
+        }
+        Ok(Self {
+            text_dir,
+            texts: Arc::default(),
+        })
+    }
+
+    /// Identifies the checkout and release of the data, so results matched against other data can be discarded.
+    ///
+    /// This is the hash of the license list, or the modification time of the text directory if there is none.
+    pub fn fingerprint(&self) -> String {
+        let list = self.text_dir.parent().map(|dir| dir.join(LICENSE_LIST));
+        let release = match list.and_then(|list| fs::read(list).ok()) {
+            Some(contents) => format!("{:x}", Sha256::digest(&contents)),
+            None => fs::metadata(&self.text_dir)
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| format!("{:?}", modified))
+                .unwrap_or_default(),
+        };
+        format!("{} {}", self.text_dir.display(), release)
+    }
+
+    /// The canonical text of a license.
+    ///
+    /// A license with an exception is the license text followed by the exception text. Texts are read from disk once.
+    pub fn license_text(&self, license: &License) -> Option<String> {
+        if let Some(text) = self.texts.read().unwrap().get(license) {
+            return text.clone();
+        }
+        let text = self.read_license_text(license);
+        self.texts
+            .write()
+            .unwrap()
+            .insert(license.clone(), text.clone());
+        text
+    }
+
+    fn read_license_text(&self, license: &License) -> Option<String> {
+        match license {
+            License::File(_) | License::Multiple(_) | License::Unspecified => None,
+            license => {
+                let mut text = String::new();
+                for id in license.to_string().split(" WITH ") {
+                    // Custom licenses may be anything, only look up known identifiers
+                    if spdx::license_id(id).is_none() && spdx::exception_id(id).is_none() {
+                        return None;
+                    }
+                    let part =
+                        fs::read_to_string(self.text_dir.join(format!("{}.txt", id))).ok()?;
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(&part);
+                }
+                Some(text)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn license_text() {
+        let dir = TempDir::new("spdx");
+        assert!(matches!(
+            SpdxData::new(&dir),
+            Err(SpdxDataError::MissingTextDir(_))
+        ));
+
+        fs::create_dir_all(dir.join(TEXT_DIR)).unwrap();
+        fs::write(dir.join(TEXT_DIR).join("BSD-4-Clause.txt"), "BSD 4 clause").unwrap();
+        fs::write(dir.join(TEXT_DIR).join("Apache-2.0.txt"), "Apache").unwrap();
+        fs::write(dir.join(TEXT_DIR).join("LLVM-exception.txt"), "LLVM").unwrap();
+        let data = SpdxData::new(&dir).unwrap();
+
+        let bsd: License = "BSD-4-Clause".parse().unwrap();
+        assert_eq!(data.license_text(&bsd).as_deref(), Some("BSD 4 clause"));
+        assert_eq!(
+            data.license_text(&License::Apache_2_0_WITH_LLVM_exception)
+                .as_deref(),
+            Some("Apache\nLLVM")
+        );
+        assert_eq!(data.license_text(&License::MIT), None);
+        assert_eq!(data.license_text(&License::Custom("Foo".into())), None);
+
+        // Texts are only read once, also by clones
+        fs::remove_dir_all(&*dir).unwrap();
+        assert_eq!(
+            data.clone().license_text(&bsd).as_deref(),
+            Some("BSD 4 clause")
+        );
+    }
+}
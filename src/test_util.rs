@@ -0,0 +1,51 @@
+//! Fixtures shared by the tests of several modules.
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+};
+
+use cargo_metadata::Package;
+
+/// An empty directory in the system temp dir that is removed when dropped, also when a test fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create the directory of the test `name`, unique to this process.
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("bundle-licenses-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A package `name` 1.0.0 under the given license, with its manifest at `dir/name/Cargo.toml`.
+pub fn package(name: &str, license: &str, dir: &Path) -> Package {
+    serde_json::from_value(serde_json::json!({
+        "name": name,
+        "version": "1.0.0",
+        "id": format!("{name} 1.0.0"),
+        "license": license,
+        "dependencies": [],
+        "targets": [],
+        "features": {},
+        "manifest_path": dir.join(name).join("Cargo.toml"),
+    }))
+    .unwrap()
+}